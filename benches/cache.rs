@@ -0,0 +1,44 @@
+mod common;
+
+use base64::engine::{general_purpose, Engine as _};
+use swift_lexer::lexer::{diagnostic::Diagnostic, swift_tokenizer::Tokenizer, token::Token, token_cache};
+
+const PATH: &str = "/Users/developer/Projects/Monorepo/Sources/Feature/Generated/Generated.swift";
+
+// The format the cache used before, JSON of the tokens encoded as base64
+fn store_json(tokens: &[Token], diagnostics: &[Diagnostic]) -> Vec<u8> {
+    let json_content = serde_json::to_string(&(tokens, diagnostics)).unwrap();
+    general_purpose::STANDARD.encode(json_content).into_bytes()
+}
+
+fn load_json(stored: &[u8]) -> (Vec<Token>, Vec<Diagnostic>) {
+    let decoded_data = general_purpose::STANDARD.decode(stored).unwrap();
+    serde_json::from_slice(&decoded_data).unwrap()
+}
+
+fn measure(name: &str, source_len: usize, store: impl Fn() -> Vec<u8>, load: impl Fn(&[u8]) -> (Vec<Token>, Vec<Diagnostic>)) {
+    let (store_time, stored) = common::best_of(&store);
+    let (load_time, _) = common::best_of(|| load(&stored));
+    let size_ratio = stored.len() as f64 / source_len as f64;
+    println!("{:>14}: {:>10} bytes {:>6.2}x source, store {:>9.2?}, load {:>9.2?}", name, stored.len(), size_ratio, store_time, load_time);
+}
+
+// Compares the size of cached tokens and the time to store and load them in each format
+fn main() {
+    for keep_trivia in [false, true] {
+        let source = common::source(20_000);
+        let tokenizer = match keep_trivia {
+            true => Tokenizer::new_non_cached().with_trivia(),
+            false => Tokenizer::new_non_cached(),
+        };
+        let (tokens, diagnostics) = { tokenizer }.tokenize_str(&source, PATH).expect("Failed to tokenize benchmark source");
+
+        println!("{} tokens{}:", tokens.len(), if keep_trivia { " with trivia" } else { "" });
+        measure("base64 json", source.len(), || store_json(&tokens, &diagnostics), load_json);
+        for (name, compress) in [("binary", false), ("binary deflate", true)] {
+            measure(name, source.len(),
+                || token_cache::encode_tokens(&tokens, &diagnostics, compress),
+                |stored| token_cache::decode_tokens(stored).unwrap());
+        }
+    }
+}
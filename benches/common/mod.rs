@@ -0,0 +1,36 @@
+use std::time::{Duration, Instant};
+
+// A mix of the constructs found in generated Swift sources
+const LINES: [&str; 8] = [
+    "/// Documentation for the declaration below",
+    "@available(iOS 15, *) public final class Generated: Base, Codable {",
+    "    let identifier_{n}: [String: Int] = [\"key\": 0x{n}, \"other\": -{n}]",
+    "    var value_{n} = \"text with \\(interpolation + {n}) inside\" // trailing comment",
+    "    func compute_{n}(_ x: Double, by y: inout Int) -> Bool { return x * 2.5e3 >= Double(y) && !flag }",
+    "    /* block comment */ let closure = { $0 &+ $1 }",
+    "    let regex = /[a-z]+_{n}/",
+    "}",
+];
+
+pub fn source(line_count: usize) -> String {
+    let mut source = String::new();
+    for n in 0..line_count {
+        source.push_str(&LINES[n % LINES.len()].replace("{n}", &n.to_string()));
+        source.push('\n');
+    }
+    source
+}
+
+// Best time out of a few runs, so the numbers are not thrown off by a single slow run
+pub fn best_of<T>(mut run: impl FnMut() -> T) -> (Duration, T) {
+    let mut best = None;
+    for _ in 0..5 {
+        let start = Instant::now();
+        let result = run();
+        let elapsed = start.elapsed();
+        if best.as_ref().is_none_or(|(fastest, _)| elapsed < *fastest) {
+            best = Some((elapsed, result));
+        }
+    }
+    best.unwrap()
+}
@@ -0,0 +1,17 @@
+mod common;
+
+use swift_lexer::lexer::swift_tokenizer::Tokenizer;
+
+// Tokenizes sources of doubling size, the time per line staying flat when lexing is linear
+fn main() {
+    let mut baseline = None;
+    for line_count in [1_250, 2_500, 5_000, 10_000, 20_000, 40_000] {
+        let source = common::source(line_count);
+        let (elapsed, _) = common::best_of(|| {
+            Tokenizer::new_non_cached().tokenize_str(&source, "generated.swift").expect("Failed to tokenize benchmark source")
+        });
+        let per_line = elapsed.as_nanos() as f64 / line_count as f64;
+        let ratio = per_line / *baseline.get_or_insert(per_line);
+        println!("{:>6} lines: {:>9.2?} {:>8.0} ns/line {:>5.2}x", line_count, elapsed, per_line, ratio);
+    }
+}
@@ -0,0 +1,253 @@
+use serde::Serialize;
+
+use crate::{lexer::token::{Token, TokenKind}, parser::parser::Parser};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Attribute {
+    pub name: String, // Without the leading `@`
+    pub arguments: Vec<AttributeArgument>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum AttributeArgument {
+    // An argument kept as its source text, such as `fooBar:` in `@objc(fooBar:)`
+    Value {
+        label: Option<String>,
+        value: String,
+    },
+    Availability(AvailabilitySpec),
+}
+
+// One comma separated part of an `@available` attribute
+#[derive(Debug, Clone, Serialize)]
+pub enum AvailabilitySpec {
+    Platform {
+        platform: String,
+        version: Option<String>,
+    },
+    AnyPlatform,
+    Unavailable,
+    NoAsync,
+    Introduced(String),
+    Deprecated(Option<String>),
+    Obsoleted(String),
+    Message(String),
+    Renamed(String),
+}
+
+// Parses every attribute at the current position, arguments only belong to an attribute when the parenthesis touches its name
+pub fn parse_attributes(p: &mut Parser) -> Vec<Attribute> {
+    let mut attributes = vec![];
+    while p.current_token().kind == TokenKind::ANNOTATION {
+        let token = p.advance().clone();
+        let name = token.value.trim_start_matches('@').to_owned();
+        if name.is_empty() {
+            p.error(&token, String::from("expected an attribute name after '@'"));
+        }
+
+        let mut arguments = vec![];
+        if p.current_token().kind == TokenKind::OPEN_PAREN && p.current_token().span.start == token.span.end {
+            for argument in parse_argument_tokens(p) {
+                if argument.is_empty() {
+                    p.error(&token, format!("expected an argument in attribute '@{}'", name));
+                } else if name == "available" {
+                    let spec = parse_availability_spec(p, &argument);
+                    arguments.push(AttributeArgument::Availability(spec));
+                } else {
+                    arguments.push(parse_attribute_argument(&argument));
+                }
+            }
+        }
+        attributes.push(Attribute { name, arguments });
+    }
+    attributes
+}
+
+// Splits the parenthesized arguments at their top level commas
+fn parse_argument_tokens(p: &mut Parser) -> Vec<Vec<Token>> {
+    let open = p.advance().clone();
+    let mut arguments = vec![vec![]];
+    let mut depth = 0;
+    loop {
+        let token = p.current_token().clone();
+        match token.kind {
+            TokenKind::EOF => {
+                p.error(&open, String::from("expected ')' to end attribute arguments"));
+                break;
+            },
+            TokenKind::CLOSE_PAREN if depth == 0 => {
+                p.advance();
+                break;
+            },
+            TokenKind::COMMA if depth == 0 => {
+                p.advance();
+                arguments.push(vec![]);
+                continue;
+            },
+            TokenKind::OPEN_PAREN | TokenKind::OPEN_BRACKET | TokenKind::OPEN_BRACE => depth += 1,
+            TokenKind::CLOSE_PAREN | TokenKind::CLOSE_BRACKET | TokenKind::CLOSE_BRACE => depth -= 1,
+            _ => {}
+        }
+        arguments.last_mut().unwrap().push(p.advance().clone());
+    }
+
+    // `@attribute()` has no arguments rather than a single empty one
+    if arguments.len() == 1 && arguments[0].is_empty() {
+        arguments.clear();
+    }
+    arguments
+}
+
+// `label: value` takes a label, but a selector such as `tableView:cellForRowAt:` is kept whole
+fn parse_attribute_argument(tokens: &[Token]) -> AttributeArgument {
+    let is_selector = tokens.get(3).is_some_and(|token| token.kind == TokenKind::COLON);
+    if tokens.len() > 2 && tokens[0].kind != TokenKind::STRING && tokens[1].kind == TokenKind::COLON && !is_selector {
+        return AttributeArgument::Value { label: Some(tokens[0].value.clone()), value: tokens_text(&tokens[2..]) };
+    }
+    AttributeArgument::Value { label: None, value: tokens_text(tokens) }
+}
+
+fn parse_availability_spec(p: &mut Parser, tokens: &[Token]) -> AvailabilitySpec {
+    let first = &tokens[0];
+    if tokens.len() > 1 && tokens[1].kind == TokenKind::COLON {
+        let value = tokens_text(&tokens[2..]);
+        let string = tokens.get(2)
+            .and_then(|token| token.string.as_ref())
+            .map(|string| string.cooked());
+        match first.value.as_str() {
+            "introduced" => return AvailabilitySpec::Introduced(value),
+            "deprecated" => return AvailabilitySpec::Deprecated(Some(value)),
+            "obsoleted" => return AvailabilitySpec::Obsoleted(value),
+            "message" => return AvailabilitySpec::Message(string.unwrap_or(value)),
+            "renamed" => return AvailabilitySpec::Renamed(string.unwrap_or(value)),
+            _ => {},
+        }
+    } else if tokens.len() == 1 {
+        match first.value.as_str() {
+            "*" => return AvailabilitySpec::AnyPlatform,
+            "unavailable" => return AvailabilitySpec::Unavailable,
+            "deprecated" => return AvailabilitySpec::Deprecated(None),
+            "noasync" => return AvailabilitySpec::NoAsync,
+            _ => {},
+        }
+    }
+
+    if first.kind != TokenKind::IDENTIFIER {
+        p.error(first, format!("expected a platform name in '@available' but found '{}'", first.value));
+    }
+    let version = match tokens.len() {
+        1 => None,
+        _ => Some(tokens_text(&tokens[1..])),
+    };
+    AvailabilitySpec::Platform { platform: first.value.clone(), version }
+}
+
+// Rebuilds the source text of the tokens, keeping a single space wherever they were apart
+pub fn tokens_text(tokens: &[Token]) -> String {
+    let mut text = String::new();
+    let mut end = None;
+    for token in tokens {
+        if end.is_some_and(|end| end < token.span.start) {
+            text.push(' ');
+        }
+        match token.kind {
+            TokenKind::STRING => text.push_str(&format!("\"{}\"", token.value)),
+            _ => text.push_str(&token.value),
+        }
+        end = Some(token.span.end);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::{statements::Stmt, types::Type}, lexer::swift_tokenizer::Tokenizer, parser::parser::parse_str};
+
+    fn attributes(source: &str) -> Vec<Attribute> {
+        let (tokens, diagnostics) = Tokenizer::new_non_cached().tokenize_str(source, "test.swift").unwrap();
+        let mut p = Parser::new(tokens, diagnostics);
+        let attributes = parse_attributes(&mut p);
+        assert!(p.diagnostics().is_empty(), "{:?}", p.diagnostics());
+        attributes
+    }
+
+    fn specs(attribute: &Attribute) -> Vec<String> {
+        attribute.arguments.iter().map(|argument| match argument {
+            AttributeArgument::Availability(spec) => format!("{:?}", spec),
+            AttributeArgument::Value { .. } => panic!("unexpected value {:?}", argument),
+        }).collect()
+    }
+
+    fn values(attribute: &Attribute) -> Vec<(Option<&str>, &str)> {
+        attribute.arguments.iter().map(|argument| match argument {
+            AttributeArgument::Value { label, value } => (label.as_deref(), value.as_str()),
+            AttributeArgument::Availability(spec) => panic!("unexpected availability {:?}", spec),
+        }).collect()
+    }
+
+    #[test]
+    fn selectors_are_kept_whole() {
+        let attributes = attributes("@objc(tableView:cellForRowAt:) @objc(a:b:) @objc(fooBar:)");
+        assert_eq!(values(&attributes[0]), [(None, "tableView:cellForRowAt:")]);
+        assert_eq!(values(&attributes[1]), [(None, "a:b:")]);
+        assert_eq!(values(&attributes[2]), [(None, "fooBar:")]);
+    }
+
+    #[test]
+    fn availability_platforms() {
+        let attributes = attributes("@available(iOS 15, macOS 12.0.1, *)");
+        assert_eq!(attributes[0].name, "available");
+        assert_eq!(specs(&attributes[0]), [
+            r#"Platform { platform: "iOS", version: Some("15") }"#,
+            r#"Platform { platform: "macOS", version: Some("12.0.1") }"#,
+            "AnyPlatform",
+        ]);
+    }
+
+    #[test]
+    fn availability_labeled_fields() {
+        let attributes = attributes(r#"@available(iOS, introduced: 13.0, deprecated: 15.0, obsoleted: 16, renamed: "newName(_:)", message: "Use \"b\"") @available(*, unavailable, noasync, deprecated)"#);
+        assert_eq!(specs(&attributes[0]), [
+            r#"Platform { platform: "iOS", version: None }"#,
+            r#"Introduced("13.0")"#,
+            r#"Deprecated(Some("15.0"))"#,
+            r#"Obsoleted("16")"#,
+            r#"Renamed("newName(_:)")"#,
+            r#"Message("Use \"b\"")"#,
+        ]);
+        assert_eq!(specs(&attributes[1]), ["AnyPlatform", "Unavailable", "NoAsync", "Deprecated(None)"]);
+    }
+
+    #[test]
+    fn labeled_and_unlabeled_values() {
+        let attributes = attributes(r#"@Environment(\.x) @Option(name: .long, help: "Count") @propertyWrapper @Foo() @Bar (x)"#);
+        assert_eq!(values(&attributes[0]), [(None, "\\.x")]);
+        assert_eq!(values(&attributes[1]), [(Some("name"), ".long"), (Some("help"), "\"Count\"")]);
+        assert_eq!(attributes[2].name, "propertyWrapper");
+        assert!(attributes[2].arguments.is_empty());
+        assert!(attributes[3].arguments.is_empty());
+        // Arguments apart from the name are not the attribute's
+        assert_eq!(attributes[4].name, "Bar");
+        assert!(attributes[4].arguments.is_empty());
+        assert_eq!(attributes.len(), 5);
+    }
+
+    #[test]
+    fn tokens_text_keeps_single_spaces() {
+        let (tokens, _) = Tokenizer::new_non_cached().tokenize_str("foo(a:   1)\n\tbar \"s\"", "test.swift").unwrap();
+        assert_eq!(tokens_text(&tokens[..tokens.len() - 1]), "foo(a: 1) bar \"s\"");
+    }
+
+    #[test]
+    fn attributed_type() {
+        let (stmt, diagnostics) = parse_str("let a: @Sendable @convention(c) Int = 1", "test.swift", false);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        let Stmt::BlockStmt { body } = stmt else { panic!("expected a block") };
+        let Stmt::VarDeclarationStmt { explicit_type, .. } = body[0].as_ref() else { panic!("expected a variable declaration") };
+        let Type::AttributedType { attributes, underlying } = explicit_type.as_ref() else { panic!("expected an attributed type") };
+        assert_eq!(attributes.iter().map(|attribute| attribute.name.as_str()).collect::<Vec<_>>(), ["Sendable", "convention"]);
+        assert_eq!(values(&attributes[1]), [(None, "c")]);
+        assert!(matches!(underlying.as_ref(), Type::SymbolType { value, .. } if value == "Int"));
+    }
+}
@@ -0,0 +1,189 @@
+use serde::Serialize;
+
+use crate::lexer::token::{Trivia, TriviaKind};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DocParameter {
+    pub name: String,
+    pub description: String,
+}
+
+// Documentation written in Swift Markup, either `///` lines or a `/** */` block, preceding a declaration
+#[derive(Debug, Clone, Serialize)]
+pub struct DocComment {
+    pub summary: String,
+    pub discussion: String,
+    pub parameters: Vec<DocParameter>,
+    pub returns: Option<String>,
+    pub throws: Option<String>,
+}
+
+// Where the text of the line being read belongs
+enum Section {
+    Summary,
+    Discussion,
+    Parameters,
+    Parameter(usize),
+    Returns,
+    Throws,
+}
+
+pub fn parse_doc_comment(trivia: &[Trivia]) -> Option<DocComment> {
+    let mut lines = vec![];
+    for piece in trivia {
+        match piece.kind {
+            TriviaKind::DocLineComment => lines.push(strip_line_comment(&piece.text)),
+            TriviaKind::DocBlockComment => lines.append(&mut strip_block_comment(&piece.text)),
+            _ => {}
+        }
+    }
+    if lines.is_empty() {
+        return None;
+    }
+
+    let mut doc = DocComment { summary: String::new(), discussion: String::new(), parameters: vec![], returns: None, throws: None };
+    let mut section = Section::Summary;
+    for line in lines {
+        let text = line.trim();
+        let indented = line.starts_with([' ', '\t']);
+        if text.is_empty() {
+            section = match section {
+                Section::Summary if doc.summary.is_empty() => Section::Summary,
+                _ => Section::Discussion,
+            };
+            if !doc.discussion.is_empty() && !doc.discussion.ends_with("\n\n") {
+                doc.discussion.push_str("\n\n");
+            }
+            continue;
+        }
+
+        if let Some(item) = text.strip_prefix(['-', '*', '+']).filter(|item| item.starts_with(' ')).map(str::trim) {
+            if let Some(parameter) = strip_field(item, "parameter ") {
+                if let Some((name, description)) = parameter.split_once(':') {
+                    doc.parameters.push(DocParameter { name: name.trim().to_string(), description: description.trim().to_string() });
+                    section = Section::Parameter(doc.parameters.len() - 1);
+                    continue;
+                }
+            } else if strip_field(item, "parameters:").is_some() {
+                section = Section::Parameters;
+                continue;
+            } else if let Some(returns) = strip_field(item, "returns:") {
+                doc.returns = Some(returns.trim().to_string());
+                section = Section::Returns;
+                continue;
+            } else if let Some(throws) = strip_field(item, "throws:") {
+                doc.throws = Some(throws.trim().to_string());
+                section = Section::Throws;
+                continue;
+            } else if let (Section::Parameters | Section::Parameter(_), true) = (&section, indented) {
+                if let Some((name, description)) = item.split_once(':') {
+                    doc.parameters.push(DocParameter { name: name.trim().to_string(), description: description.trim().to_string() });
+                    section = Section::Parameter(doc.parameters.len() - 1);
+                    continue;
+                }
+            }
+            section = Section::Discussion;
+        }
+
+        // Lines following a field without starting a new item continue its description
+        let target = match section {
+            Section::Summary => &mut doc.summary,
+            Section::Parameter(index) => &mut doc.parameters[index].description,
+            Section::Returns => doc.returns.get_or_insert_with(String::new),
+            Section::Throws => doc.throws.get_or_insert_with(String::new),
+            Section::Discussion | Section::Parameters => {
+                section = Section::Discussion;
+                &mut doc.discussion
+            },
+        };
+        if !target.is_empty() && !target.ends_with('\n') {
+            target.push(' ');
+        }
+        target.push_str(text);
+    }
+    doc.discussion = doc.discussion.trim_end().to_string();
+    Some(doc)
+}
+
+// Matches the case insensitive name of a markup field, returning the rest of the item
+fn strip_field<'a>(item: &'a str, field: &str) -> Option<&'a str> {
+    let prefix = item.get(..field.len())?;
+    if prefix.eq_ignore_ascii_case(field) {
+        return Some(&item[field.len()..]);
+    }
+    None
+}
+
+fn strip_line_comment(comment: &str) -> String {
+    let text = comment.trim_start_matches("///");
+    text.strip_prefix(' ').unwrap_or(text).trim_end().to_string()
+}
+
+// Removes the delimiters of a block comment and the `*` that commonly starts each of its lines
+fn strip_block_comment(comment: &str) -> Vec<String> {
+    let text = comment.strip_prefix("/**").unwrap_or(comment);
+    let text = text.strip_suffix("*/").unwrap_or(text);
+    let mut lines: Vec<String> = text.lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            match trimmed.strip_prefix('*') {
+                Some(rest) => rest.strip_prefix(' ').unwrap_or(rest).trim_end().to_string(),
+                None => line.trim_end().to_string(),
+            }
+        })
+        .collect();
+    while lines.first().is_some_and(|line| line.trim().is_empty()) {
+        lines.remove(0);
+    }
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::swift_tokenizer::Tokenizer;
+
+    fn doc(source: &str) -> DocComment {
+        let (tokens, _) = Tokenizer::new_non_cached().tokenize_str(source, "test.swift").unwrap();
+        parse_doc_comment(&tokens[0].leading_trivia).expect("expected a doc comment")
+    }
+
+    fn parameters(doc: &DocComment) -> Vec<(&str, &str)> {
+        doc.parameters.iter().map(|parameter| (parameter.name.as_str(), parameter.description.as_str())).collect()
+    }
+
+    #[test]
+    fn parameters_section() {
+        let doc = doc("/// Adds two numbers.\n///\n/// Overflow traps.\n/// - Parameters:\n///   - a: The first number,\n///     which is continued.\n///   - b: The second number.\n/// - returns: The sum.\n/// - Throws: Nothing.\nfunc add(a: Int, b: Int) -> Int");
+        assert_eq!(doc.summary, "Adds two numbers.");
+        assert_eq!(doc.discussion, "Overflow traps.");
+        assert_eq!(parameters(&doc), [("a", "The first number, which is continued."), ("b", "The second number.")]);
+        assert_eq!(doc.returns.as_deref(), Some("The sum."));
+        assert_eq!(doc.throws.as_deref(), Some("Nothing."));
+    }
+
+    #[test]
+    fn separate_parameters() {
+        let doc = doc("/// Moves.\n/// - Parameter from: Where to start\n///   and how.\n/// - parameter to: Where to end.\n/// - Unrelated item\nfunc move()");
+        assert_eq!(parameters(&doc), [("from", "Where to start and how."), ("to", "Where to end.")]);
+        assert_eq!(doc.discussion, "- Unrelated item");
+    }
+
+    #[test]
+    fn block_comment_with_leading_stars() {
+        let doc = doc("/**\n * Loads the file.\n *\n * Reads it all at once.\n * - Parameter path: The file.\n * - Returns: Its contents.\n */\nfunc load(path: String) -> String");
+        assert_eq!(doc.summary, "Loads the file.");
+        assert_eq!(doc.discussion, "Reads it all at once.");
+        assert_eq!(parameters(&doc), [("path", "The file.")]);
+        assert_eq!(doc.returns.as_deref(), Some("Its contents."));
+    }
+
+    #[test]
+    fn plain_comments_are_not_docs() {
+        let (tokens, _) = Tokenizer::new_non_cached().with_trivia().tokenize_str("// Not a doc\n/* Nor this */\nlet a = 1", "test.swift").unwrap();
+        assert!(parse_doc_comment(&tokens[0].leading_trivia).is_none());
+    }
+}
@@ -1,6 +1,6 @@
 use serde::Serialize;
 
-use crate::{lexer::token::{Token, TokenKind}, parser::{lookup::UNARY, parser::{parse_expr, Parser}}};
+use crate::{lexer::token::{token_can_be_name, Fixity, Radix, StringSegment, Token, TokenKind}, parser::{lookup::UNARY, parser::{parse_expr, parse_nested_expr, Parser}}};
 
 #[derive(Debug, Serialize)]
 pub enum Expr {
@@ -8,16 +8,26 @@ pub enum Expr {
     // Literal Expressions
     FloatExpr {
         value: f64,
+        literal: String,
     },
     IntergerExpr {
         value: i64,
+        literal: String,
     },
     StringExpr {
         value: String,
+        literal: String,
+    },
+    InterpolatedStringExpr {
+        parts: Vec<Box<Expr>>,
     },
     SymbolExpr {
         value: String,
     },
+    RegexExpr {
+        pattern: String,
+        delimiter_depth: u64,
+    },
 
     // Complex Expressions
     BinaryExpr {
@@ -54,25 +64,46 @@ pub enum Expr {
     ArrayLiteralExpr {
         contents: Vec<Box<Expr>>,
     },
+
+    // A key path such as `\Foo.bar?.baz`, the root type being inferred when omitted
+    KeyPathExpr {
+        root: Option<String>,
+        components: Vec<String>,
+    },
 }
 
 pub fn parse_primary_expr(p: &mut Parser) -> Expr {
-    println!("Parsing primary expression: {:?}", p.current_token());
     match p.current_token().kind {
         TokenKind::NUMBER => {
-            let token = p.advance();
-            if let Some(integer) = token.value.parse::<i64>().ok() {
-                return Expr::IntergerExpr { value: integer };
+            let token = p.advance().clone();
+            let (expr, error) = decode_number(&token.value, token.radix.unwrap_or(Radix::Decimal));
+            if let Some(message) = error {
+                p.error(&token, message);
             }
-            if let Some(float) = token.value.parse::<f64>().ok() {
-                return Expr::FloatExpr { value: float };
-            }
-            panic!("Failed to parse number literal from token {:?}", token);
+            return expr;
         },
         TokenKind::STRING => {
-            return Expr::StringExpr { value: p.advance().value.clone() };
+            let token = p.advance().clone();
+            if let Some(string) = token.string.clone().filter(|string| string.is_interpolated()) {
+                let mut parts = vec![];
+                for segment in string.segments {
+                    match segment {
+                        StringSegment::Literal { value, .. } if value.is_empty() => {},
+                        StringSegment::Literal { value, cooked } => parts.push(Box::new(Expr::StringExpr { value: cooked, literal: value })),
+                        StringSegment::Interpolation { tokens } => parts.push(Box::new(parse_nested_expr(p, tokens))),
+                    }
+                }
+                return Expr::InterpolatedStringExpr { parts };
+            }
+            let value = token.string.map(|string| string.cooked()).unwrap_or(token.value.clone());
+            return Expr::StringExpr { value, literal: token.value };
+        },
+        TokenKind::REGEX => {
+            let token = p.advance().clone();
+            let delimiter_depth = token.regex.map_or(0, |regex| regex.delimiter_depth);
+            return Expr::RegexExpr { pattern: token.value, delimiter_depth };
         },
-        TokenKind::IDENTIFIER => {
+        TokenKind::IDENTIFIER | TokenKind::DOLLAR_IDENTIFIER => {
             return Expr::SymbolExpr { value: p.advance().value.clone() };
         }
         unhandled => {
@@ -85,4 +116,93 @@ pub fn parse_prefix_expr(p: &mut Parser) -> Expr {
     let opperator = p.advance().clone();
     let expr = parse_expr(p, UNARY);
     Expr::PrefixExpr { opperator, right: Box::new(expr) }
-}
\ No newline at end of file
+}
+
+pub fn parse_key_path_expr(p: &mut Parser) -> Expr {
+    _ = p.expect(TokenKind::BACKSLASH);
+    let mut root = None;
+    if p.current_token().kind == TokenKind::IDENTIFIER {
+        root = Some(p.advance().value.clone());
+    }
+
+    let mut components: Vec<String> = vec![];
+    loop {
+        match p.current_token().kind {
+            TokenKind::DOT if token_can_be_name(p.next_token()) => {
+                p.advance();
+                components.push(p.advance().value.clone());
+            },
+            // Optional chaining and force unwrapping apply to the component they follow
+            TokenKind::OPTIONAL | TokenKind::NOT if p.current_token().fixity == Some(Fixity::Postfix) => {
+                let marker = p.advance().value.clone();
+                match components.last_mut() {
+                    Some(component) => component.push_str(&marker),
+                    None => components.push(marker),
+                }
+            },
+            _ => break,
+        }
+    }
+
+    if root.is_none() && components.is_empty() {
+        let token = p.current_token().clone();
+        p.error(&token, String::from("expected a type or member in key path"));
+    }
+    Expr::KeyPathExpr { root, components }
+}
+
+// Decodes a number literal written in any radix, along with an error when its value can not be represented
+fn decode_number(literal: &str, radix: Radix) -> (Expr, Option<String>) {
+    let negative = literal.starts_with('-');
+    let digits = literal.trim_start_matches('-').replace('_', "");
+    let digits = if radix == Radix::Decimal { digits.as_str() } else { &digits[2..] };
+    let is_float = match radix {
+        Radix::Decimal => digits.contains(['.', 'e', 'E']),
+        Radix::Hexadecimal => digits.contains(['.', 'p', 'P']),
+        _ => false,
+    };
+
+    if is_float {
+        let mut value = match radix {
+            Radix::Hexadecimal => decode_hex_float(digits),
+            _ => digits.parse::<f64>().unwrap_or(f64::INFINITY),
+        };
+        if negative {
+            value = -value;
+        }
+        let error = if value.is_infinite() {
+            Some(format!("float literal '{}' overflows when stored into 'Double'", literal))
+        } else {
+            None
+        };
+        return (Expr::FloatExpr { value, literal: literal.to_string() }, error);
+    }
+
+    let magnitude = u64::from_str_radix(digits, radix.base()).ok().map(|value| value as i128);
+    let value = magnitude
+        .map(|value| if negative { -value } else { value })
+        .and_then(|value| i64::try_from(value).ok());
+    match value {
+        Some(value) => (Expr::IntergerExpr { value, literal: literal.to_string() }, None),
+        None => (
+            Expr::IntergerExpr { value: 0, literal: literal.to_string() },
+            Some(format!("integer literal '{}' overflows when stored into 'Int'", literal)),
+        ),
+    }
+}
+
+// Decodes the digits of a hexadecimal float, such as `1.8p-3`, where the exponent is a power of two
+fn decode_hex_float(digits: &str) -> f64 {
+    let (mantissa, exponent) = digits.split_once(['p', 'P']).unwrap_or((digits, "0"));
+    let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let mut value = 0.0;
+    for digit in whole.chars().chain(fraction.chars()) {
+        value = value * 16.0 + digit.to_digit(16).unwrap_or(0) as f64;
+    }
+    if value == 0.0 {
+        return 0.0;
+    }
+    // Exponents too large to parse saturate towards their sign, underflowing to zero or overflowing to infinity
+    let exponent = exponent.parse::<i32>().unwrap_or(if exponent.starts_with('-') { i32::MIN } else { i32::MAX });
+    value * 2f64.powi(exponent.saturating_sub(4 * fraction.len() as i32))
+}
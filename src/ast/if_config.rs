@@ -0,0 +1,345 @@
+use std::{cmp::Ordering, collections::HashSet};
+
+use serde::Serialize;
+
+use crate::{lexer::token::TokenKind, parser::parser::{parse_stmt, Parser}};
+
+use super::{attributes::tokens_text, statements::Stmt};
+
+// One `#if`, `#elseif` or `#else` branch, the `#else` branch having no condition
+#[derive(Debug, Serialize)]
+pub struct IfConfigClause {
+    pub condition: Option<BuildCondition>,
+    pub body: Vec<Box<Stmt>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum BuildCondition {
+    Flag(String),
+    Boolean(bool),
+    // A platform check such as `os(iOS)`, `arch(arm64)` or `canImport(UIKit)`
+    Platform {
+        check: String,
+        value: String,
+    },
+    // A version check such as `swift(>=5.9)` or `compiler(<6)`
+    Version {
+        check: String,
+        at_least: bool,
+        version: String,
+    },
+    Not(Box<BuildCondition>),
+    And(Box<BuildCondition>, Box<BuildCondition>),
+    Or(Box<BuildCondition>, Box<BuildCondition>),
+}
+
+// The compilation flags and target a build is made for, deciding which `#if` branches are active
+#[derive(Debug, Clone, Default)]
+pub struct BuildConfiguration {
+    pub flags: HashSet<String>,
+    pub os: String,
+    pub arch: String,
+    pub target_environment: Option<String>,
+    pub swift_version: String,
+    pub compiler_version: String,
+    pub importable_modules: HashSet<String>,
+    pub features: HashSet<String>,
+    pub attributes: HashSet<String>,
+}
+
+impl BuildConfiguration {
+    pub fn new(os: &str, arch: &str, swift_version: &str) -> BuildConfiguration {
+        BuildConfiguration {
+            os: os.to_owned(),
+            arch: arch.to_owned(),
+            swift_version: swift_version.to_owned(),
+            compiler_version: swift_version.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn evaluate(&self, condition: &BuildCondition) -> bool {
+        match condition {
+            BuildCondition::Flag(flag) => self.flags.contains(flag),
+            BuildCondition::Boolean(value) => *value,
+            BuildCondition::Platform { check, value } => match check.as_str() {
+                "os" => self.os == *value,
+                "arch" => self.arch == *value,
+                "targetEnvironment" => self.target_environment.as_ref() == Some(value),
+                // A submodule can be imported whenever its top level module can
+                "canImport" => self.importable_modules.contains(value.split('.').next().unwrap_or(value)),
+                "hasFeature" => self.features.contains(value),
+                "hasAttribute" => self.attributes.contains(value),
+                _ => false,
+            },
+            BuildCondition::Version { check, at_least, version } => {
+                let current = match check.as_str() {
+                    "swift" => &self.swift_version,
+                    _ => &self.compiler_version,
+                };
+                let newer = compare_versions(current, version) != Ordering::Less;
+                newer == *at_least
+            },
+            BuildCondition::Not(condition) => !self.evaluate(condition),
+            BuildCondition::And(left, right) => self.evaluate(left) && self.evaluate(right),
+            BuildCondition::Or(left, right) => self.evaluate(left) || self.evaluate(right),
+        }
+    }
+
+    // Replaces every `#if` block in the statement by the body of its active branch
+    pub fn active_stmt(&self, stmt: Stmt) -> Stmt {
+        let mut stmt = match stmt {
+            Stmt::IfConfigStmt { .. } => return Stmt::BlockStmt { body: self.active_body(vec![Box::new(stmt)]) },
+            stmt => stmt,
+        };
+        match &mut stmt {
+            Stmt::BlockStmt { body } |
+            Stmt::FunctionDeclarationStmt { body, .. } => {
+                *body = self.active_body(std::mem::take(body));
+            }
+            Stmt::ClassDeclarationStmt { body, .. } |
+            Stmt::ForeachStmt { body, .. } => {
+                **body = self.active_stmt(std::mem::replace(body, Stmt::None));
+            }
+            Stmt::IfStmt { consequent, alternate, .. } => {
+                **consequent = self.active_stmt(std::mem::replace(consequent, Stmt::None));
+                **alternate = self.active_stmt(std::mem::replace(alternate, Stmt::None));
+            }
+            _ => {}
+        }
+        stmt
+    }
+
+    fn active_body(&self, body: Vec<Box<Stmt>>) -> Vec<Box<Stmt>> {
+        let mut active = vec![];
+        for stmt in body {
+            match *stmt {
+                Stmt::IfConfigStmt { clauses } => {
+                    let clause = clauses.into_iter()
+                        .find(|clause| clause.condition.as_ref().is_none_or(|condition| self.evaluate(condition)));
+                    if let Some(clause) = clause {
+                        active.append(&mut self.active_body(clause.body));
+                    }
+                }
+                stmt => active.push(Box::new(self.active_stmt(stmt))),
+            }
+        }
+        active
+    }
+}
+
+// Compares dotted versions, missing components counting as zero so `5.9` equals `5.9.0`
+fn compare_versions(left: &str, right: &str) -> Ordering {
+    let left: Vec<u64> = left.split('.').map(|part| part.parse().unwrap_or(0)).collect();
+    let right: Vec<u64> = right.split('.').map(|part| part.parse().unwrap_or(0)).collect();
+    for i in 0..left.len().max(right.len()) {
+        let ordering = left.get(i).unwrap_or(&0).cmp(right.get(i).unwrap_or(&0));
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    Ordering::Equal
+}
+
+pub fn parse_if_config_stmt(p: &mut Parser) -> Stmt {
+    let mut clauses = vec![];
+    let mut directive = p.expect(TokenKind::POUND_IF);
+    loop {
+        let condition = match directive.kind {
+            TokenKind::POUND_ELSE => None,
+            _ => Some(parse_build_condition(p)),
+        };
+
+        let mut body = vec![];
+        while p.has_tokens() && !matches!(p.current_token().kind, TokenKind::POUND_ELSEIF | TokenKind::POUND_ELSE | TokenKind::POUND_ENDIF) {
+            body.push(Box::new(parse_stmt(p)));
+        }
+        clauses.push(IfConfigClause { condition, body });
+
+        if !p.has_tokens() {
+            let token = p.current_token().clone();
+            p.error(&token, String::from("expected '#endif' to end conditional compilation block"));
+            break;
+        }
+        let next = p.advance().clone();
+        match next.kind {
+            TokenKind::POUND_ENDIF => break,
+            _ if directive.kind == TokenKind::POUND_ELSE => {
+                p.error(&next, format!("unexpected '{}' after '#else'", next.value));
+            }
+            _ => {}
+        }
+        directive = next;
+    }
+    Stmt::IfConfigStmt { clauses }
+}
+
+// Reports a directive that continues or ends an `#if` block when no block is open
+pub fn parse_unmatched_if_config_stmt(p: &mut Parser) -> Stmt {
+    let token = p.advance().clone();
+    p.error(&token, format!("unexpected '{}' without a matching '#if'", token.value));
+    Stmt::None
+}
+
+fn parse_build_condition(p: &mut Parser) -> BuildCondition {
+    let mut left = parse_and_condition(p);
+    while p.current_token().kind == TokenKind::OR {
+        p.advance();
+        left = BuildCondition::Or(Box::new(left), Box::new(parse_and_condition(p)));
+    }
+    left
+}
+
+fn parse_and_condition(p: &mut Parser) -> BuildCondition {
+    let mut left = parse_unary_condition(p);
+    while p.current_token().kind == TokenKind::AND {
+        p.advance();
+        left = BuildCondition::And(Box::new(left), Box::new(parse_unary_condition(p)));
+    }
+    left
+}
+
+fn parse_unary_condition(p: &mut Parser) -> BuildCondition {
+    let token = p.current_token().clone();
+    match token.kind {
+        TokenKind::NOT => {
+            p.advance();
+            BuildCondition::Not(Box::new(parse_unary_condition(p)))
+        },
+        TokenKind::OPEN_PAREN => {
+            p.advance();
+            let condition = parse_build_condition(p);
+            if p.current_token().kind == TokenKind::CLOSE_PAREN {
+                p.advance();
+            } else {
+                let token = p.current_token().clone();
+                p.error(&token, String::from("expected ')' in conditional compilation condition"));
+            }
+            condition
+        },
+        TokenKind::TRUE | TokenKind::FALSE => {
+            p.advance();
+            BuildCondition::Boolean(token.kind == TokenKind::TRUE)
+        },
+        TokenKind::IDENTIFIER if p.next_token().kind == TokenKind::OPEN_PAREN => {
+            p.advance();
+            p.advance();
+            parse_check_condition(p, token.value)
+        },
+        TokenKind::IDENTIFIER => {
+            p.advance();
+            BuildCondition::Flag(token.value)
+        },
+        _ => {
+            p.error(&token, String::from("expected a condition in conditional compilation block"));
+            BuildCondition::Boolean(false)
+        }
+    }
+}
+
+// Parses the arguments of a check like `os(iOS)` or `swift(>=5.9)` up to its closing parenthesis
+fn parse_check_condition(p: &mut Parser, check: String) -> BuildCondition {
+    let mut tokens = vec![];
+    while p.has_tokens() && p.current_token().kind != TokenKind::CLOSE_PAREN {
+        tokens.push(p.advance().clone());
+    }
+    if p.current_token().kind == TokenKind::CLOSE_PAREN {
+        p.advance();
+    } else {
+        let token = p.current_token().clone();
+        p.error(&token, format!("expected ')' to end '{}' condition", check));
+    }
+
+    match check.as_str() {
+        "swift" | "compiler" => {
+            let at_least = match tokens.first().map(|token| token.kind) {
+                Some(TokenKind::GREATER_EQUALS) => true,
+                Some(TokenKind::LESS) => false,
+                _ => {
+                    let token = tokens.first().unwrap_or(p.current_token()).clone();
+                    p.error(&token, format!("expected '>=' or '<' in '{}' condition", check));
+                    return BuildCondition::Boolean(false);
+                }
+            };
+            BuildCondition::Version { check, at_least, version: tokens_text(&tokens[1..]) }
+        },
+        _ => {
+            // Only the first argument decides the check, `canImport` may be followed by a version label
+            let end = tokens.iter().position(|token| token.kind == TokenKind::COMMA).unwrap_or(tokens.len());
+            BuildCondition::Platform { check, value: tokens_text(&tokens[..end]) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::swift_tokenizer::Tokenizer, parser::parser::parse_str};
+
+    fn condition(source: &str) -> BuildCondition {
+        let (tokens, diagnostics) = Tokenizer::new_non_cached().tokenize_str(source, "test.swift").unwrap();
+        let mut p = Parser::new(tokens, diagnostics);
+        let condition = parse_build_condition(&mut p);
+        assert!(p.diagnostics().is_empty(), "{:?}", p.diagnostics());
+        assert!(!p.has_tokens());
+        condition
+    }
+
+    fn identifiers(stmt: &Stmt) -> Vec<&str> {
+        let Stmt::BlockStmt { body } = stmt else { panic!("expected a block") };
+        body.iter().map(|stmt| match stmt.as_ref() {
+            Stmt::VarDeclarationStmt { identifier, .. } => identifier.as_str(),
+            stmt => panic!("unexpected statement {:?}", stmt),
+        }).collect()
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(format!("{:?}", condition("!A && B || C")), r#"Or(And(Not(Flag("A")), Flag("B")), Flag("C"))"#);
+        assert_eq!(format!("{:?}", condition("A || B && !(C || D)")), r#"Or(Flag("A"), And(Flag("B"), Not(Or(Flag("C"), Flag("D")))))"#);
+
+        let mut configuration = BuildConfiguration::new("macOS", "arm64", "6.0");
+        configuration.flags.insert(String::from("C"));
+        assert!(configuration.evaluate(&condition("!A && B || C")));
+        assert!(!configuration.evaluate(&condition("A || B && !(C || D)")));
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(compare_versions("5.9", "5.9.0"), Ordering::Equal);
+        assert_eq!(compare_versions("5.10", "5.9"), Ordering::Greater);
+        assert_eq!(compare_versions("5", "5.0.1"), Ordering::Less);
+
+        let configuration = BuildConfiguration::new("iOS", "arm64", "5.9.0");
+        assert!(configuration.evaluate(&condition("swift(>=5.9)")));
+        assert!(!configuration.evaluate(&condition("swift(<5.9)")));
+        assert!(configuration.evaluate(&condition("compiler(<6)")));
+        assert!(!configuration.evaluate(&condition("compiler(>=6)")));
+    }
+
+    #[test]
+    fn platforms() {
+        let mut configuration = BuildConfiguration::new("iOS", "arm64", "6.0");
+        configuration.importable_modules.insert(String::from("UIKit"));
+        configuration.target_environment = Some(String::from("simulator"));
+        assert!(configuration.evaluate(&condition("canImport(UIKit.UIView)")));
+        assert!(configuration.evaluate(&condition("canImport(UIKit, _version: 1.2)")));
+        assert!(!configuration.evaluate(&condition("canImport(AppKit)")));
+        assert!(configuration.evaluate(&condition("os(iOS) && arch(arm64) && targetEnvironment(simulator)")));
+        assert!(!configuration.evaluate(&condition("os(macOS)")));
+    }
+
+    #[test]
+    fn first_active_branch_is_kept() {
+        let source = "#if os(iOS)\nlet a: Int\n#elseif DEBUG || !swift(>=5.9)\nlet b: Int\n#elseif DEBUG\nlet c: Int\n#else\nlet d: Int\n#endif\nlet e: Int";
+        let (stmt, diagnostics) = parse_str(source, "test.swift", false);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        let mut configuration = BuildConfiguration::new("macOS", "arm64", "6.0");
+        assert_eq!(identifiers(&configuration.active_stmt(parse_str(source, "test.swift", false).0)), ["d", "e"]);
+        configuration.flags.insert(String::from("DEBUG"));
+        assert_eq!(identifiers(&configuration.active_stmt(parse_str(source, "test.swift", false).0)), ["b", "e"]);
+        configuration.os = String::from("iOS");
+        assert_eq!(identifiers(&configuration.active_stmt(stmt)), ["a", "e"]);
+    }
+}
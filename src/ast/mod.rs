@@ -1,3 +1,6 @@
+pub mod attributes;
+pub mod docs;
 pub mod expressions;
+pub mod if_config;
 pub mod statements;
 pub mod types;
\ No newline at end of file
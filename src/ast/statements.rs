@@ -2,7 +2,7 @@ use serde::Serialize;
 
 use crate::{lexer::token::TokenKind, parser::{lookup::ASSIGNMENT, parser::{parse_expr, parse_stmt, Parser}}};
 
-use super::{expressions::Expr, types::{parse_type, Type}};
+use super::{attributes::{parse_attributes, Attribute}, docs::DocComment, expressions::Expr, if_config::IfConfigClause, types::{parse_type, Type}};
 
 #[derive(Debug, Serialize)]
 pub enum Stmt {
@@ -17,6 +17,8 @@ pub enum Stmt {
     },
 
     VarDeclarationStmt {
+        doc: Option<DocComment>,
+        attributes: Vec<Attribute>,
         modifiers: Vec<String>,
         identifier: String,
         constant: bool,
@@ -29,6 +31,8 @@ pub enum Stmt {
     },
 
     FunctionDeclarationStmt {
+        doc: Option<DocComment>,
+        attributes: Vec<Attribute>,
         parameters: Vec<Box<Type>>,
         name: String,
         body: Vec<Box<Stmt>>,
@@ -53,10 +57,16 @@ pub enum Stmt {
     },
 
     ClassDeclarationStmt {
+        doc: Option<DocComment>,
+        attributes: Vec<Attribute>,
         name: String,
         implements: Vec<String>,
         body: Box<Stmt>,
     },
+
+    IfConfigStmt {
+        clauses: Vec<IfConfigClause>,
+    },
 }
 
 fn is_modifier(token: &TokenKind) -> bool {
@@ -108,12 +118,26 @@ pub fn parse_var_decl_stmt(p: &mut Parser) -> Stmt {
         _ => {}
     }
 
-    return Stmt::VarDeclarationStmt { modifiers: vec![], identifier: symbol_name.clone(), constant: is_constant, assigned_value: Box::new(assignment), explicit_type: Box::new(explicit_type) };
+    return Stmt::VarDeclarationStmt { doc: None, attributes: vec![], modifiers: vec![], identifier: symbol_name.clone(), constant: is_constant, assigned_value: Box::new(assignment), explicit_type: Box::new(explicit_type) };
+}
+
+// A contextual modifier such as `override` only acts as one when another modifier or a declaration follows it
+pub fn starts_with_contextual_modifier(p: &Parser) -> bool {
+    if !p.contextual_keyword().is_some_and(|keyword| keyword.is_declaration_modifier()) {
+        return false;
+    }
+    let next = p.next_token();
+    match next.kind {
+        TokenKind::IDENTIFIER => next.contextual_keyword.is_some_and(|keyword| keyword.is_declaration_modifier()),
+        TokenKind::VAR | TokenKind::LET | TokenKind::FUNC | TokenKind::INIT | TokenKind::CLASS |
+        TokenKind::STRUCT | TokenKind::ENUM | TokenKind::CASE => true,
+        kind => is_modifier(&kind),
+    }
 }
 
 pub fn parse_prefix_stmt(p: &mut Parser) -> Stmt {
     let mut modifiers = vec![];
-    while p.has_tokens() && is_modifier(&p.current_token().kind) {
+    while p.has_tokens() && (is_modifier(&p.current_token().kind) || starts_with_contextual_modifier(p)) {
         modifiers.push(p.advance().value.clone());
     }
     let mut stmt = parse_stmt(p);
@@ -124,4 +148,34 @@ pub fn parse_prefix_stmt(p: &mut Parser) -> Stmt {
         _ => {}
     }
     stmt
+}
+
+pub fn parse_attributed_stmt(p: &mut Parser) -> Stmt {
+    let mut attributes = parse_attributes(p);
+    let mut stmt = parse_stmt(p);
+    match &mut stmt {
+        Stmt::VarDeclarationStmt { attributes: existing_attributes, .. } |
+        Stmt::FunctionDeclarationStmt { attributes: existing_attributes, .. } |
+        Stmt::ClassDeclarationStmt { attributes: existing_attributes, .. } => {
+            attributes.append(existing_attributes);
+            *existing_attributes = attributes;
+        }
+        _ => {
+            let token = p.previous_token().clone();
+            p.error(&token, String::from("attributes can only be applied to declarations"));
+        }
+    }
+    stmt
+}
+
+// Attaches documentation to a declaration that has not already been documented
+pub fn attach_doc(stmt: &mut Stmt, doc: Option<DocComment>) {
+    match stmt {
+        Stmt::VarDeclarationStmt { doc: existing, .. } |
+        Stmt::FunctionDeclarationStmt { doc: existing, .. } |
+        Stmt::ClassDeclarationStmt { doc: existing, .. } if existing.is_none() => {
+            *existing = doc;
+        }
+        _ => {}
+    }
 }
\ No newline at end of file
@@ -2,6 +2,8 @@ use serde::Serialize;
 
 use crate::parser::{parser::Parser, types::nud};
 
+use super::attributes::Attribute;
+
 #[derive(Debug, Clone, Serialize)]
 pub enum Type {
     Unknown,
@@ -39,6 +41,11 @@ pub enum Type {
         generics: Vec<Box<Type>>,
         name: String,
     },
+
+    AttributedType {
+        attributes: Vec<Attribute>,
+        underlying: Box<Type>,
+    },
 }
 
 pub fn parse_type(p: &mut Parser) -> Type {
@@ -0,0 +1,28 @@
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
+// A recoverable error found while processing a source file, located at the point it was found
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub file: String,
+    pub line: u64,
+    pub col: u64,
+}
+
+impl Diagnostic {
+    pub fn new(message: String, file: String, line: u64, col: u64) -> Diagnostic {
+        Diagnostic {
+            message,
+            file,
+            line,
+            col
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}: error: {}", self.file, self.line, self.col, self.message)
+    }
+}
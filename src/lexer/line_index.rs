@@ -0,0 +1,178 @@
+use super::token::Span;
+
+// A line and column in a source file, both starting at 1 like the positions of tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: u64,
+    pub col: u64,
+}
+
+impl Position {
+    pub fn new(line: u64, col: u64) -> Position {
+        Position { line, col }
+    }
+}
+
+// Index of where each line of a source file starts, used to convert between byte offsets and positions.
+// Columns can be counted in UTF-8 bytes, in characters as tokens are, or in UTF-16 code units as LSP clients expect
+pub struct LineIndex {
+    source: String,
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> LineIndex {
+        let mut line_starts = vec![0];
+        let bytes = source.as_bytes();
+        for (i, byte) in bytes.iter().enumerate() {
+            match byte {
+                b'\n' => line_starts.push(i + 1),
+                b'\r' if bytes.get(i + 1) != Some(&b'\n') => line_starts.push(i + 1),
+                _ => {}
+            }
+        }
+
+        LineIndex {
+            source: source.to_string(),
+            line_starts,
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    // The byte offset each line starts at, a line break belonging to the line it ends
+    pub fn line_start(&self, line: u64) -> Option<usize> {
+        self.line_starts.get((line as usize).checked_sub(1)?).copied()
+    }
+
+    // Offsets past the end of the source or inside a character are clamped onto the position before them
+    pub fn offset_to_utf8(&self, offset: usize) -> Position {
+        let offset = self.clamp(offset);
+        let (line, line_start) = self.line_of(offset);
+        Position::new(line, (offset - line_start) as u64 + 1)
+    }
+
+    pub fn offset_to_char(&self, offset: usize) -> Position {
+        let offset = self.clamp(offset);
+        let (line, line_start) = self.line_of(offset);
+        Position::new(line, self.source[line_start..offset].chars().count() as u64 + 1)
+    }
+
+    pub fn offset_to_utf16(&self, offset: usize) -> Position {
+        let offset = self.clamp(offset);
+        let (line, line_start) = self.line_of(offset);
+        let col: usize = self.source[line_start..offset].chars().map(char::len_utf16).sum();
+        Position::new(line, col as u64 + 1)
+    }
+
+    pub fn utf8_to_offset(&self, position: Position) -> Option<usize> {
+        let line_start = self.line_start(position.line)?;
+        let offset = line_start + (position.col as usize).checked_sub(1)?;
+        if offset > self.line_end(position.line) || !self.source.is_char_boundary(offset) {
+            return None;
+        }
+        Some(offset)
+    }
+
+    pub fn char_to_offset(&self, position: Position) -> Option<usize> {
+        self.offset_of_col(position, |_| 1)
+    }
+
+    pub fn utf16_to_offset(&self, position: Position) -> Option<usize> {
+        self.offset_of_col(position, char::len_utf16)
+    }
+
+    pub fn span_to_utf16(&self, span: Span) -> (Position, Position) {
+        (self.offset_to_utf16(span.start as usize), self.offset_to_utf16(span.end as usize))
+    }
+
+    // Moves the offset back onto the end of the source or the start of the character it is in
+    fn clamp(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    fn line_of(&self, offset: usize) -> (u64, usize) {
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        (line as u64, self.line_starts[line - 1])
+    }
+
+    // The offset of the end of the line's content, before its line break
+    fn line_end(&self, line: u64) -> usize {
+        match self.line_starts.get(line as usize) {
+            Some(next) if self.source[..*next].ends_with("\r\n") => next - 2,
+            Some(next) => next - 1,
+            None => self.source.len(),
+        }
+    }
+
+    fn offset_of_col(&self, position: Position, width: fn(char) -> usize) -> Option<usize> {
+        let line_start = self.line_start(position.line)?;
+        let target = (position.col as usize).checked_sub(1)?;
+        let mut col = 0;
+        for (i, c) in self.source[line_start..self.line_end(position.line)].char_indices() {
+            if col == target {
+                return Some(line_start + i);
+            }
+            if col > target {
+                return None;
+            }
+            col += width(c);
+        }
+        (col == target).then_some(self.line_end(position.line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_breaks() {
+        let index = LineIndex::new("a\r\nb\rc\nd");
+        assert_eq!(index.line_count(), 4);
+        assert_eq!([1, 2, 3, 4].map(|line| index.line_start(line)), [Some(0), Some(3), Some(5), Some(7)]);
+        assert_eq!(index.line_start(5), None);
+        assert_eq!(index.offset_to_utf8(3), Position::new(2, 1));
+        assert_eq!(index.offset_to_utf8(5), Position::new(3, 1));
+        // The line break belongs to the line it ends
+        assert_eq!(index.offset_to_utf8(2), Position::new(1, 3));
+        assert_eq!(index.utf8_to_offset(Position::new(1, 2)), Some(1));
+        assert_eq!(index.utf8_to_offset(Position::new(1, 3)), None);
+        assert_eq!(index.utf8_to_offset(Position::new(3, 2)), Some(6));
+        assert_eq!(index.char_to_offset(Position::new(2, 2)), Some(4));
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        let index = LineIndex::new("a😀b\né");
+        assert_eq!(index.offset_to_utf8(5), Position::new(1, 6));
+        assert_eq!(index.offset_to_char(5), Position::new(1, 3));
+        assert_eq!(index.offset_to_utf16(5), Position::new(1, 4));
+        assert_eq!(index.utf16_to_offset(Position::new(1, 4)), Some(5));
+        assert_eq!(index.char_to_offset(Position::new(1, 3)), Some(5));
+        // A column between the two halves of the pair is no character's
+        assert_eq!(index.utf16_to_offset(Position::new(1, 3)), None);
+        assert_eq!(index.utf8_to_offset(Position::new(1, 3)), None);
+        assert_eq!(index.offset_to_utf16(9), Position::new(2, 2));
+        assert_eq!(index.utf16_to_offset(Position::new(2, 2)), Some(9));
+    }
+
+    #[test]
+    fn offsets_are_clamped() {
+        let index = LineIndex::new("ab\né");
+        // Inside `é` and past the end of the source
+        assert_eq!(index.offset_to_char(4), Position::new(2, 1));
+        assert_eq!(index.offset_to_utf16(100), Position::new(2, 2));
+        assert_eq!(index.offset_to_utf8(100), Position::new(2, 3));
+        assert_eq!(index.span_to_utf16(Span::new(1, 100)), (Position::new(1, 2), Position::new(2, 2)));
+        assert_eq!(index.utf16_to_offset(Position::new(2, 3)), None);
+        assert_eq!(index.utf16_to_offset(Position::new(1, 0)), None);
+        assert_eq!(index.char_to_offset(Position::new(3, 1)), None);
+    }
+}
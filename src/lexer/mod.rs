@@ -1,3 +1,6 @@
+pub mod diagnostic;
+pub mod line_index;
 pub mod swift_tokenizer;
 pub mod token;
+pub mod token_cache;
 mod tokenizer;
\ No newline at end of file
@@ -1,61 +1,53 @@
-use core::str;
-use std::{collections::HashMap, fs::{File, OpenOptions}, io::{self, Read, SeekFrom, Seek, Write}, path::Path};
+use std::{collections::{HashMap, HashSet}, fs::{self, File, OpenOptions}, io::{self, BufWriter, Write}, path::{Path, PathBuf}, process};
 use sha2::{Digest, Sha256};
 use base64::engine::{general_purpose, Engine as _};
 
-use super::{token::Token, tokenizer};
+use super::{diagnostic::Diagnostic, token::Token, token_cache, tokenizer};
+
+// Bumped whenever the layout of the cache file changes
+const CACHE_FORMAT_VERSION: u32 = 5;
+const CACHE_MAGIC: &str = "swift_lexer-cache";
+// Bumped whenever a change to the lexer changes the tokens it gives for a source, so tokens cached before are not reused
+const LEXER_VERSION: u32 = 3;
 
 // Tokenizer that can be configured to cache tokens for files based on file hashes
 pub struct Tokenizer {
     use_cache: bool,
-    cache_file: Option<File>,
-    token_map: HashMap<String, String>,
+    keep_trivia: bool,
+    compress_cache: bool,
+    cache_path: Option<PathBuf>,
+    token_map: HashMap<String, Vec<u8>>,
+    hash_map: HashMap<String, String>,
+    changed: HashSet<String>, // Entries not yet saved to the cache file
+}
+
+// What could be read from a cache file
+#[derive(Default)]
+struct CacheContents {
+    token_map: HashMap<String, Vec<u8>>,
     hash_map: HashMap<String, String>,
+    stale_entries: usize, // Entries replaced by a later one or unreadable
+    appendable: bool, // Whether the file was written by this lexer and ends with a whole entry
 }
 
 impl Tokenizer {
     fn new(use_cache: bool, cache_path: Option<&Path>) -> Tokenizer {
-        let mut file: Option<File> = None;
-        let mut token_map = HashMap::new();
-        let mut hash_map = HashMap::new();
-        if let Some(path) = cache_path {
-            if let Some(mut read) = File::open(path).ok() {
-                let mut content = String::new();
-                read.read_to_string(&mut content).unwrap();
-                (token_map, hash_map) = Tokenizer::read_cache_file(&content);
-            }
-            file = Some(OpenOptions::new()
-                .write(true)
-                .truncate(true)
-                .create(true)
-                .open(&path)
-                .expect("Failed to open or create cache file"));
-        }
+        let contents = cache_path
+            .and_then(|path| fs::read(path).ok())
+            .map(|content| read_cache_file(&content))
+            .unwrap_or_default();
 
         Tokenizer {
             use_cache,
-            cache_file: file,
-            token_map,
-            hash_map,
+            keep_trivia: false,
+            compress_cache: false,
+            cache_path: cache_path.map(Path::to_path_buf),
+            token_map: contents.token_map,
+            hash_map: contents.hash_map,
+            changed: HashSet::new(),
         }
     }
 
-    fn read_cache_file(content: &str) -> (HashMap<String, String>, HashMap<String, String>) {
-        let mut token_map =  HashMap::new();
-        let mut hash_map = HashMap::new();
-        for line in content.lines() {
-            let split: Vec<&str> =  line.split(',').collect();
-            let file_name = split[0];
-            let file_hash = split[1];
-            let token_content = split[2];
-
-            token_map.insert(file_name.to_string(), token_content.to_string());
-            hash_map.insert(file_name.to_string(), file_hash.to_string());
-        }
-
-        (token_map, hash_map)
-    }
-
     pub fn new_cached(cache_path: &Path) -> Tokenizer {
         Tokenizer::new(true, Some(cache_path))
     }
@@ -64,86 +56,393 @@ impl Tokenizer {
         Tokenizer::new(false, None)
     }
 
-    pub fn tokenize(&mut self, file_path: &Path) -> io::Result<Vec<Token>> {
-        if let Some(cached_tokens) = self.cached_tokens_for(file_path)? {
-            return Ok(cached_tokens);
+    // Tokenizes losslessly, keeping the text of every token along with its surrounding whitespace and comments
+    pub fn with_trivia(mut self) -> Tokenizer {
+        self.keep_trivia = true;
+        self
+    }
+
+    // Deflates the tokens stored in the cache, making it smaller at the cost of slower stores and loads
+    pub fn with_compression(mut self) -> Tokenizer {
+        self.compress_cache = true;
+        self
+    }
+
+    // Tokenizes the file, returning the tokens along with any recoverable lexer errors found in it
+    pub fn tokenize(&mut self, file_path: &Path) -> io::Result<(Vec<Token>, Vec<Diagnostic>)> {
+        let source = fs::read_to_string(file_path)?;
+        self.tokenize_str(&source, &file_path.to_string_lossy())
+    }
+
+    // Tokenizes source held in memory, the name standing in for a file path in tokens, diagnostics and the cache
+    pub fn tokenize_str(&mut self, source: &str, name: &str) -> io::Result<(Vec<Token>, Vec<Diagnostic>)> {
+        let hash = self.content_hash(source);
+        if let Some(cached) = self.cached_tokens_for(name, &hash) {
+            return Ok(cached);
         }
-        println!("Have to read file");
-        let (file_hash, tokens) = tokenize(file_path)?;
-        self.cache_if_needed(file_path, file_hash, &tokens)?;
-        Ok(tokens)
+        let (tokens, diagnostics) = if self.keep_trivia {
+            tokenizer::tokenize_with_trivia(source, name.to_string())
+        } else {
+            tokenizer::tokenize(source, name.to_string())
+        };
+        self.cache_if_needed(name, hash, &tokens, &diagnostics)?;
+        Ok((tokens, diagnostics))
     }
 
-    fn cache_if_needed(&mut self, file_path: &Path, file_hash: String, tokens: &Vec<Token>) -> io::Result<()> {
+    fn cache_if_needed(&mut self, name: &str, file_hash: String, tokens: &[Token], diagnostics: &[Diagnostic]) -> io::Result<()> {
         if !self.use_cache {
             return Ok(())
         }
 
-        let file_name = file_path.to_string_lossy().to_string();
-        let json_content = serde_json::to_string(tokens)
-            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        let encoded_tokens = general_purpose::STANDARD.encode(json_content);
+        let file_name = self.cache_key(name);
+        let encoded_tokens = token_cache::encode_tokens(tokens, diagnostics, self.compress_cache);
         self.hash_map.insert(file_name.to_string(), file_hash);
-        self.token_map.insert(file_name, encoded_tokens);
+        self.token_map.insert(file_name.clone(), encoded_tokens);
+        self.changed.insert(file_name);
         Ok(())
     }
 
-    fn cached_tokens_for(&mut self, file_path: &Path) -> io::Result<Option<Vec<Token>>> {
-        if self.use_cache {
-            let mut file = File::open(file_path)?;
-            let mut hasher = Sha256::new();
-
-            io::copy(&mut file, &mut hasher)?;
-            let hash_bytes = hasher.finalize();
-            let hash = general_purpose::STANDARD.encode(hash_bytes);
-            let file_name = file_path.to_string_lossy().to_string();
-            if self.hash_map.contains_key(&file_name) && self.token_map.contains_key(&file_name) && *self.hash_map.get(&file_name).unwrap() == hash {
-                let encoded_data = self.token_map.get(&file_name).unwrap();
-                let decoded_data = general_purpose::STANDARD.decode(encoded_data)
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                let content = str::from_utf8(&decoded_data)
-                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-                let tokens: Vec<Token> = serde_json::from_str(content)?;
-                return Ok(Some(tokens));
+    // Tokens with and without trivia are cached separately for the same file, behind a separator no path can hold
+    fn cache_key(&self, name: &str) -> String {
+        if self.keep_trivia {
+            return format!("{}\0trivia", name);
+        }
+        name.to_string()
+    }
+
+    // Cached tokens are only reused while the source they were lexed from has the same hash, the hash also covering
+    // the lexer version and options so tokens lexed any other way are not reused either
+    fn content_hash(&self, source: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(LEXER_VERSION.to_le_bytes());
+        hasher.update([self.keep_trivia as u8]);
+        hasher.update(source.as_bytes());
+        general_purpose::STANDARD.encode(hasher.finalize())
+    }
+
+    // Entries that no longer decode are dropped so the source is lexed again rather than failing
+    fn cached_tokens_for(&mut self, name: &str, hash: &str) -> Option<(Vec<Token>, Vec<Diagnostic>)> {
+        if !self.use_cache {
+            return None;
+        }
+        let file_name = self.cache_key(name);
+        if self.hash_map.get(&file_name).map(String::as_str) != Some(hash) {
+            return None;
+        }
+
+        let cached = self.token_map.get(&file_name)
+            .and_then(|encoded_data| token_cache::decode_tokens(encoded_data));
+        if cached.is_none() {
+            self.token_map.remove(&file_name);
+            self.hash_map.remove(&file_name);
+            self.changed.remove(&file_name);
+        }
+        cached
+    }
+
+    // Saves the entries cached since the last save. They are appended to the cache file, which is only written anew
+    // when it can not be appended to or is mostly outdated entries, then by renaming a complete file over it so a
+    // crash never leaves the cache half written.
+    // Processes sharing the cache take turns through its lock file, each first taking in the entries saved by the
+    // others since it read the cache so none of them are lost when the file is written anew
+    pub fn save(&mut self) -> io::Result<()> {
+        let Some(path) = self.cache_path.clone() else {
+            return Ok(());
+        };
+        if !self.use_cache || self.changed.is_empty() {
+            return Ok(());
+        }
+
+        let _lock = lock_cache(&path)?;
+        let stored = match fs::read(&path) {
+            Ok(content) => read_cache_file(&content),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => CacheContents::default(),
+            Err(error) => return Err(error),
+        };
+        let mut stale_entries = stored.stale_entries;
+        let mut stored_tokens = stored.token_map;
+        for (file_name, file_hash) in stored.hash_map {
+            if self.changed.contains(&file_name) {
+                stale_entries += 1;
+            } else if let Some(token_content) = stored_tokens.remove(&file_name) {
+                self.token_map.insert(file_name.clone(), token_content);
+                self.hash_map.insert(file_name, file_hash);
+            }
+        }
+
+        if !stored.appendable || stale_entries > self.hash_map.len() {
+            self.write_cache_file(&path)?;
+        } else {
+            let mut entries = vec![];
+            for file_name in &self.changed {
+                if let (Some(file_hash), Some(token_content)) = (self.hash_map.get(file_name), self.token_map.get(file_name)) {
+                    write_cache_entry(&mut entries, file_name, file_hash, token_content)?;
+                }
+            }
+            // A single write, so an append cut short only ever damages the last entries
+            let mut file = OpenOptions::new().append(true).open(&path)?;
+            file.write_all(&entries)?;
+            file.sync_data()?;
+        }
+        self.changed.clear();
+        Ok(())
+    }
+
+    fn write_cache_file(&self, path: &Path) -> io::Result<()> {
+        let temp_path = sibling_path(path, &format!("{}.tmp", process::id()));
+
+        let written = File::create(&temp_path).and_then(|file| {
+            let mut writer = BufWriter::new(file);
+            writeln!(writer, "{}", cache_header())?;
+            for (file_name, file_hash) in &self.hash_map {
+                if let Some(token_content) = self.token_map.get(file_name) {
+                    write_cache_entry(&mut writer, file_name, file_hash, token_content)?;
+                }
+            }
+            writer.into_inner()?.sync_all()
+        });
+        match written.and_then(|_| fs::rename(&temp_path, path)) {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                let _ = fs::remove_file(&temp_path);
+                Err(error)
             }
         }
-        Ok(None)
     }
 }
 
+// Errors can not be reported from here, call `save` to be told of them
 impl Drop for Tokenizer {
     fn drop(&mut self) {
-        if !self.use_cache { return; }
-        let mut file: &File;
-        if let Some(cache_file) = &self.cache_file {
-            file = cache_file;
-        } else {
-            return;
+        let _ = self.save();
+    }
+}
+
+// Reads every intact entry of a cache written by this exact lexer, anything else is left to be lexed again.
+// Entries are only ever appended, so a later entry for a file replaces any earlier one
+fn read_cache_file(content: &[u8]) -> CacheContents {
+    let mut contents = CacheContents::default();
+    let Some(header_end) = content.iter().position(|b| *b == b'\n') else {
+        return contents;
+    };
+    if content[..header_end] != *cache_header().as_bytes() {
+        return contents;
+    }
+
+    let mut rest = &content[header_end + 1..];
+    while !rest.is_empty() {
+        // Reading stops at the first entry whose lengths run past the end, as nothing after it can be trusted.
+        // This is usually an append cut short, or one still being made by another process
+        let Some((entry, len)) = read_cache_entry(rest) else {
+            return contents;
+        };
+        rest = &rest[len..];
+        // An entry that fails its checksum is skipped, its source will be lexed again
+        match entry {
+            Some(entry) => {
+                contents.token_map.insert(entry.file_name.clone(), entry.token_content);
+                if contents.hash_map.insert(entry.file_name, entry.file_hash).is_some() {
+                    contents.stale_entries += 1;
+                }
+            },
+            None => contents.stale_entries += 1,
         }
+    }
+    contents.appendable = true;
+    contents
+}
 
-        for (file_name, file_hash) in &self.hash_map {
-            if let Some(token_content) = &self.token_map.get(file_name) {
-                writeln!(file, "{},{},{}", file_name, file_hash, token_content).unwrap();
-            }
+// Waits until no other process is writing the cache, which lasts for as long as the returned file is open.
+// The lock is on a file of its own, as the cache file itself is replaced whenever it is written anew
+fn lock_cache(path: &Path) -> io::Result<File> {
+    let lock_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(sibling_path(path, "lock"))?;
+    lock_file.lock()?;
+    Ok(lock_file)
+}
+
+// A path next to the cache file, named after it
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{}", extension));
+    path.with_file_name(file_name)
+}
+
+// A cache written by another version of the lexer or in another layout is read as empty
+fn cache_header() -> String {
+    format!("{} {} {} {}", CACHE_MAGIC, CACHE_FORMAT_VERSION, env!("CARGO_PKG_VERSION"), LEXER_VERSION)
+}
+
+// Entries are written as length prefixed fields followed by a SHA-256 checksum of them, `<len><name><len><hash>
+// <len><tokens><checksum>`, the lengths being varints
+fn write_cache_entry(file: &mut impl Write, file_name: &str, file_hash: &str, token_content: &[u8]) -> io::Result<()> {
+    let mut fields = vec![];
+    for field in [file_name.as_bytes(), file_hash.as_bytes(), token_content] {
+        token_cache::write_varint(&mut fields, field.len() as u64);
+        fields.extend_from_slice(field);
+    }
+    file.write_all(&fields)?;
+    file.write_all(&Sha256::digest(&fields))
+}
+
+struct CacheEntry {
+    file_name: String,
+    file_hash: String,
+    token_content: Vec<u8>,
+}
+
+// Reads the entry at the start of the content along with its length, the entry being missing when its checksum fails
+fn read_cache_entry(content: &[u8]) -> Option<(Option<CacheEntry>, usize)> {
+    let (file_name, rest) = read_cache_field(content)?;
+    let (file_hash, rest) = read_cache_field(rest)?;
+    let (token_content, rest) = read_cache_field(rest)?;
+    let fields_len = content.len() - rest.len();
+    let checksum = rest.get(..32)?;
+    if *checksum != *Sha256::digest(&content[..fields_len]) {
+        return Some((None, fields_len + 32));
+    }
+
+    let entry = match (String::from_utf8(file_name.to_vec()), String::from_utf8(file_hash.to_vec())) {
+        (Ok(file_name), Ok(file_hash)) => Some(CacheEntry { file_name, file_hash, token_content: token_content.to_vec() }),
+        _ => None,
+    };
+    Some((entry, fields_len + 32))
+}
+
+fn read_cache_field(content: &[u8]) -> Option<(&[u8], &[u8])> {
+    let mut len: u64 = 0;
+    let mut read = 0;
+    loop {
+        let byte = *content.get(read)?;
+        len |= ((byte & 0x7f) as u64).checked_shl(7 * read as u32)?;
+        read += 1;
+        if byte & 0x80 == 0 {
+            break;
         }
     }
+    let end = read.checked_add(usize::try_from(len).ok()?)?;
+    Some((content.get(read..end)?, &content[end..]))
 }
 
-fn tokenize(file_name: &Path) -> io::Result<(String, Vec<Token>)> {
-    let mut file = File::open(file_name)?;
-    let mut hasher = Sha256::new();
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    io::copy(&mut file, &mut hasher)?;
-    let hash_bytes = hasher.finalize();
-    let hash = general_purpose::STANDARD.encode(hash_bytes);
+    // A fresh directory for the cache of a single test
+    fn cache_path(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("swift_lexer-{}-{}", test, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("cache")
+    }
 
-    file.seek(SeekFrom::Start(0))?;
+    fn save_source(path: &Path, name: &str, source: &str) {
+        let mut tokenizer = Tokenizer::new_cached(path);
+        tokenizer.tokenize_str(source, name).unwrap();
+        tokenizer.save().unwrap();
+    }
+
+    fn is_cached(path: &Path, name: &str, source: &str) -> bool {
+        let mut tokenizer = Tokenizer::new_cached(path);
+        let hash = tokenizer.content_hash(source);
+        tokenizer.cached_tokens_for(name, &hash).is_some()
+    }
+
+    #[test]
+    fn appends_changed_entries() {
+        let path = cache_path("append");
+        save_source(&path, "a.swift", "let a = 1");
+        save_source(&path, "b.swift", "let b = 1");
+        let before = fs::read(&path).unwrap();
+        save_source(&path, "b.swift", "let b = 2");
+        let after = fs::read(&path).unwrap();
+
+        assert!(after.len() > before.len() && after.starts_with(&before));
+        assert!(is_cached(&path, "a.swift", "let a = 1"));
+        assert!(is_cached(&path, "b.swift", "let b = 2"));
+        assert!(!is_cached(&path, "b.swift", "let b = 1"));
+        let contents = read_cache_file(&after);
+        assert_eq!((contents.hash_map.len(), contents.stale_entries, contents.appendable), (2, 1, true));
+    }
+
+    #[test]
+    fn truncated_last_entry_is_lexed_again() {
+        let path = cache_path("truncated");
+        save_source(&path, "a.swift", "let a = 1");
+        save_source(&path, "b.swift", "let b = 1");
+        let content = fs::read(&path).unwrap();
+        fs::write(&path, &content[..content.len() - 5]).unwrap();
+
+        let contents = read_cache_file(&fs::read(&path).unwrap());
+        assert!(!contents.appendable);
+        assert!(is_cached(&path, "a.swift", "let a = 1"));
+        assert!(!is_cached(&path, "b.swift", "let b = 1"));
+
+        // The next save writes the whole file anew rather than appending after the damaged entry
+        save_source(&path, "b.swift", "let b = 1");
+        let contents = read_cache_file(&fs::read(&path).unwrap());
+        assert_eq!((contents.hash_map.len(), contents.stale_entries, contents.appendable), (2, 0, true));
+    }
+
+    #[test]
+    fn entry_failing_its_checksum_is_skipped() {
+        let path = cache_path("checksum");
+        save_source(&path, "a.swift", "let a = 1");
+        save_source(&path, "b.swift", "let b = 1");
+        let mut content = fs::read(&path).unwrap();
+        // The first entry's name follows its length byte on the line after the header
+        let name_start = content.iter().position(|b| *b == b'\n').unwrap() + 2;
+        content[name_start] ^= 1;
+        fs::write(&path, &content).unwrap();
 
-    let mut file_data = Vec::new();
-    file.read_to_end(&mut file_data)?;
-    let file_content = String::from_utf8(file_data)
-        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let contents = read_cache_file(&content);
+        assert_eq!((contents.hash_map.len(), contents.stale_entries, contents.appendable), (1, 1, true));
+        assert!(!is_cached(&path, "a.swift", "let a = 1"));
+        assert!(is_cached(&path, "b.swift", "let b = 1"));
+    }
+
+    #[test]
+    fn mostly_stale_cache_is_compacted() {
+        let path = cache_path("compaction");
+        save_source(&path, "a.swift", "let a = 0");
+        for i in 1..4 {
+            save_source(&path, "a.swift", &format!("let a = {}", i));
+        }
+
+        let contents = read_cache_file(&fs::read(&path).unwrap());
+        assert_eq!((contents.hash_map.len(), contents.stale_entries), (1, 1));
+        assert!(is_cached(&path, "a.swift", "let a = 3"));
+        // The file was written anew through a temporary file, which is gone once renamed over the cache
+        let mut files: Vec<_> = fs::read_dir(path.parent().unwrap()).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["cache", "cache.lock"]);
+    }
+
+    #[test]
+    fn trivia_entries_are_kept_apart() {
+        let path = cache_path("trivia");
+        save_source(&path, "a.swift#trivia", "let a = 1");
+        let mut tokenizer = Tokenizer::new_cached(&path).with_trivia();
+        tokenizer.tokenize_str("let a = 1", "a.swift").unwrap();
+        tokenizer.save().unwrap();
 
-    let tokens = tokenizer::tokenize(&file_content, file_name.to_string_lossy().to_string());
-    Ok((hash, tokens))
-}
\ No newline at end of file
+        let contents = read_cache_file(&fs::read(&path).unwrap());
+        assert_eq!((contents.hash_map.len(), contents.stale_entries), (2, 0));
+        assert!(is_cached(&path, "a.swift#trivia", "let a = 1"));
+    }
+
+    #[test]
+    fn incompatible_cache_is_ignored() {
+        let path = cache_path("incompatible");
+        fs::write(&path, "swift_lexer-cache 1 0.0.0 lexer\n1:a 1:b 1:c").unwrap();
+        assert!(!is_cached(&path, "a", "let a = 1"));
+
+        save_source(&path, "a.swift", "let a = 1");
+        assert!(is_cached(&path, "a.swift", "let a = 1"));
+    }
+}
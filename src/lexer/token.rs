@@ -60,6 +60,123 @@ static RESERVED_TOKENS: phf::Map<&'static str, TokenKind> = phf_map! {
     "super" => TokenKind::SUPER,
 };
 
+// Words that are only keywords in certain positions, they are lexed as identifiers tagged with their keyword
+static CONTEXTUAL_KEYWORDS: phf::Map<&'static str, ContextualKeyword> = phf_map! {
+    "async" => ContextualKeyword::Async,
+    "await" => ContextualKeyword::Await,
+    "reasync" => ContextualKeyword::Reasync,
+    "get" => ContextualKeyword::Get,
+    "set" => ContextualKeyword::Set,
+    "willSet" => ContextualKeyword::WillSet,
+    "didSet" => ContextualKeyword::DidSet,
+    "inout" => ContextualKeyword::Inout,
+    "mutating" => ContextualKeyword::Mutating,
+    "nonmutating" => ContextualKeyword::Nonmutating,
+    "override" => ContextualKeyword::Override,
+    "required" => ContextualKeyword::Required,
+    "convenience" => ContextualKeyword::Convenience,
+    "optional" => ContextualKeyword::Optional,
+    "indirect" => ContextualKeyword::Indirect,
+    "dynamic" => ContextualKeyword::Dynamic,
+    "distributed" => ContextualKeyword::Distributed,
+    "nonisolated" => ContextualKeyword::Nonisolated,
+    "isolated" => ContextualKeyword::Isolated,
+    "package" => ContextualKeyword::Package,
+    "associatedtype" => ContextualKeyword::Associatedtype,
+    "subscript" => ContextualKeyword::Subscript,
+    "operator" => ContextualKeyword::Operator,
+    "precedencegroup" => ContextualKeyword::Precedencegroup,
+    "prefix" => ContextualKeyword::Prefix,
+    "postfix" => ContextualKeyword::Postfix,
+    "infix" => ContextualKeyword::Infix,
+    "associativity" => ContextualKeyword::Associativity,
+    "higherThan" => ContextualKeyword::HigherThan,
+    "lowerThan" => ContextualKeyword::LowerThan,
+    "assignment" => ContextualKeyword::Assignment,
+    "left" => ContextualKeyword::Left,
+    "right" => ContextualKeyword::Right,
+    "none" => ContextualKeyword::None,
+    "consuming" => ContextualKeyword::Consuming,
+    "borrowing" => ContextualKeyword::Borrowing,
+    "consume" => ContextualKeyword::Consume,
+    "copy" => ContextualKeyword::Copy,
+    "discard" => ContextualKeyword::Discard,
+    "sending" => ContextualKeyword::Sending,
+    "macro" => ContextualKeyword::Macro,
+    "each" => ContextualKeyword::Each,
+    "unsafe" => ContextualKeyword::Unsafe,
+};
+
+static OPERATOR_TOKENS: phf::Map<&'static str, TokenKind> = phf_map! {
+    "..." => TokenKind::DOT_DOT_DOT,
+    "..<" => TokenKind::RANGE,
+    "." => TokenKind::DOT,
+    "??" => TokenKind::DEFAULTING,
+    "?" => TokenKind::OPTIONAL,
+    "->" => TokenKind::ARROW,
+    "=" => TokenKind::ASSIGNMENT,
+    "==" => TokenKind::EQUALS,
+    "!=" => TokenKind::NOT_EQUALS,
+    "===" => TokenKind::IDENTICAL,
+    "!==" => TokenKind::NOT_IDENTICAL,
+    "~=" => TokenKind::PATTERN_MATCH,
+    ">" => TokenKind::GREATER,
+    "<" => TokenKind::LESS,
+    ">=" => TokenKind::GREATER_EQUALS,
+    "<=" => TokenKind::LESS_EQUALS,
+    "!" => TokenKind::NOT,
+    "&&" => TokenKind::AND,
+    "||" => TokenKind::OR,
+    "+" => TokenKind::PLUS,
+    "-" => TokenKind::MINUS,
+    "*" => TokenKind::STAR,
+    "/" => TokenKind::SLASH,
+    "%" => TokenKind::PERCENT,
+    "+=" => TokenKind::PLUS_EQUALS,
+    "-=" => TokenKind::MINUS_EQUALS,
+    "*=" => TokenKind::STAR_EQUALS,
+    "/=" => TokenKind::SLASH_EQUALS,
+    "%=" => TokenKind::PERCENT_EQUALS,
+    "&+" => TokenKind::OVERFLOW_PLUS,
+    "&-" => TokenKind::OVERFLOW_MINUS,
+    "&*" => TokenKind::OVERFLOW_STAR,
+    "&+=" => TokenKind::OVERFLOW_PLUS_EQUALS,
+    "&-=" => TokenKind::OVERFLOW_MINUS_EQUALS,
+    "&*=" => TokenKind::OVERFLOW_STAR_EQUALS,
+    "&" => TokenKind::AMPERSAND,
+    "|" => TokenKind::PIPE,
+    "^" => TokenKind::CARET,
+    "~" => TokenKind::TILDE,
+    "<<" => TokenKind::SHIFT_LEFT,
+    ">>" => TokenKind::SHIFT_RIGHT,
+    "&<<" => TokenKind::MASKING_SHIFT_LEFT,
+    "&>>" => TokenKind::MASKING_SHIFT_RIGHT,
+    "&=" => TokenKind::AMPERSAND_EQUALS,
+    "|=" => TokenKind::PIPE_EQUALS,
+    "^=" => TokenKind::CARET_EQUALS,
+    "<<=" => TokenKind::SHIFT_LEFT_EQUALS,
+    ">>=" => TokenKind::SHIFT_RIGHT_EQUALS,
+    "&<<=" => TokenKind::MASKING_SHIFT_LEFT_EQUALS,
+    "&>>=" => TokenKind::MASKING_SHIFT_RIGHT_EQUALS,
+    ".==" => TokenKind::POINTWISE_EQUALS,
+    ".!=" => TokenKind::POINTWISE_NOT_EQUALS,
+    ".<" => TokenKind::POINTWISE_LESS,
+    ".<=" => TokenKind::POINTWISE_LESS_EQUALS,
+    ".>" => TokenKind::POINTWISE_GREATER,
+    ".>=" => TokenKind::POINTWISE_GREATER_EQUALS,
+    ".&" => TokenKind::POINTWISE_AND,
+    ".|" => TokenKind::POINTWISE_OR,
+    ".^" => TokenKind::POINTWISE_XOR,
+    ".~" => TokenKind::POINTWISE_NOT,
+    ".&=" => TokenKind::POINTWISE_AND_EQUALS,
+    ".|=" => TokenKind::POINTWISE_OR_EQUALS,
+    ".^=" => TokenKind::POINTWISE_XOR_EQUALS,
+};
+
+pub fn operator_to_token(symbol: &str) -> Option<&TokenKind> {
+    OPERATOR_TOKENS.get(symbol)
+}
+
 pub fn string_to_token(symbol: &str) -> &TokenKind {
     let token = RESERVED_TOKENS.get(symbol);
     if let Some(token) = token {
@@ -68,6 +185,10 @@ pub fn string_to_token(symbol: &str) -> &TokenKind {
     &TokenKind::IDENTIFIER
 }
 
+pub fn string_to_contextual_keyword(symbol: &str) -> Option<ContextualKeyword> {
+    CONTEXTUAL_KEYWORDS.get(symbol).copied()
+}
+
 pub fn token_can_be_name(token: &Token) -> bool {
     if RESERVED_TOKENS.contains_key(&token.value) {
         return true;
@@ -84,6 +205,7 @@ pub enum TokenKind {
     VAR,
     LET,
     IDENTIFIER,
+    DOLLAR_IDENTIFIER, // An implicit closure parameter such as `$0` or a projected value such as `$value`
     IMPORT,
 
     // Protections
@@ -166,6 +288,55 @@ pub enum TokenKind {
     PLUS_EQUALS,
     MINUS_EQUALS,
     PERCENT,
+    ARROW,
+
+    // Arithmetic
+    PLUS,
+    MINUS,
+    SLASH,
+    STAR_EQUALS,
+    SLASH_EQUALS,
+    PERCENT_EQUALS,
+    OVERFLOW_PLUS,
+    OVERFLOW_MINUS,
+    OVERFLOW_STAR,
+    OVERFLOW_PLUS_EQUALS,
+    OVERFLOW_MINUS_EQUALS,
+    OVERFLOW_STAR_EQUALS,
+
+    // Bitwise
+    AMPERSAND,
+    PIPE,
+    CARET,
+    TILDE,
+    SHIFT_LEFT,
+    SHIFT_RIGHT,
+    MASKING_SHIFT_LEFT,
+    MASKING_SHIFT_RIGHT,
+    AMPERSAND_EQUALS,
+    PIPE_EQUALS,
+    CARET_EQUALS,
+    SHIFT_LEFT_EQUALS,
+    SHIFT_RIGHT_EQUALS,
+    MASKING_SHIFT_LEFT_EQUALS,
+    MASKING_SHIFT_RIGHT_EQUALS,
+
+    // SIMD pointwise
+    POINTWISE_EQUALS,
+    POINTWISE_NOT_EQUALS,
+    POINTWISE_LESS,
+    POINTWISE_LESS_EQUALS,
+    POINTWISE_GREATER,
+    POINTWISE_GREATER_EQUALS,
+    POINTWISE_AND,
+    POINTWISE_OR,
+    POINTWISE_XOR,
+    POINTWISE_NOT,
+    POINTWISE_AND_EQUALS,
+    POINTWISE_OR_EQUALS,
+    POINTWISE_XOR_EQUALS,
+
+    OPERATOR, // Any operator without a dedicated kind, such as a user declared `<*>`
 
     // Equalities
     EQUALS,
@@ -177,23 +348,235 @@ pub enum TokenKind {
     NOT_EQUALS,
     OR,
     AND,
+    IDENTICAL,
+    NOT_IDENTICAL,
+    PATTERN_MATCH,
 
     ANNOTATION,
     MACRO,
+    // Conditional compilation directives
+    POUND_IF,
+    POUND_ELSEIF,
+    POUND_ELSE,
+    POUND_ENDIF,
+    BACKSLASH, // Introduces a key-path expression
     STRING,
+    REGEX,
     NUMBER,
+    ERROR, // Source text the lexer could not recognize, reported as a diagnostic
     EOF,
 
     ANYTHING, // A * character to be used to match to alpha reserved types
 }
 
+// A keyword only in some positions, `repeat` stays a reserved word and also introduces pack expansions
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Copy, Hash)]
+pub enum ContextualKeyword {
+    Async,
+    Await,
+    Reasync,
+    Get,
+    Set,
+    WillSet,
+    DidSet,
+    Inout,
+    Mutating,
+    Nonmutating,
+    Override,
+    Required,
+    Convenience,
+    Optional,
+    Indirect,
+    Dynamic,
+    Distributed,
+    Nonisolated,
+    Isolated,
+    Package,
+    Associatedtype,
+    Subscript,
+    Operator,
+    Precedencegroup,
+    Prefix,
+    Postfix,
+    Infix,
+    Associativity,
+    HigherThan,
+    LowerThan,
+    Assignment,
+    Left,
+    Right,
+    None,
+    Consuming,
+    Borrowing,
+    Consume,
+    Copy,
+    Discard,
+    Sending,
+    Macro,
+    Each,
+    Unsafe,
+}
+
+impl ContextualKeyword {
+    // Whether the keyword can modify a declaration it precedes, like `override func`
+    pub fn is_declaration_modifier(&self) -> bool {
+        matches!(self,
+            ContextualKeyword::Mutating | ContextualKeyword::Nonmutating | ContextualKeyword::Override |
+            ContextualKeyword::Required | ContextualKeyword::Convenience | ContextualKeyword::Optional |
+            ContextualKeyword::Indirect | ContextualKeyword::Dynamic | ContextualKeyword::Distributed |
+            ContextualKeyword::Nonisolated | ContextualKeyword::Package | ContextualKeyword::Prefix |
+            ContextualKeyword::Postfix | ContextualKeyword::Infix | ContextualKeyword::Consuming |
+            ContextualKeyword::Borrowing
+        )
+    }
+}
+
+// How an operator applies to its operands, decided by the whitespace around it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Copy)]
+pub enum Fixity {
+    Prefix,
+    Infix,
+    Postfix,
+}
+
+// The base a number literal was written in
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Copy)]
+pub enum Radix {
+    Binary,
+    Octal,
+    Decimal,
+    Hexadecimal,
+}
+
+impl Radix {
+    pub fn base(&self) -> u32 {
+        match self {
+            Radix::Binary => 2,
+            Radix::Octal => 8,
+            Radix::Decimal => 10,
+            Radix::Hexadecimal => 16,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Radix::Binary => "binary",
+            Radix::Octal => "octal",
+            Radix::Decimal => "decimal",
+            Radix::Hexadecimal => "hexadecimal",
+        }
+    }
+}
+
+// A piece of a string literal, the literal text between interpolations or the tokens of an interpolation
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum StringSegment {
+    Literal {
+        value: String, // The text as written in source
+        cooked: String, // The text with its escapes decoded
+    },
+    Interpolation {
+        tokens: Vec<Token>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StringLiteral {
+    pub segments: Vec<StringSegment>,
+    pub delimiter_depth: u64, // Number of `#` around the quotes of a raw string
+    pub multiline: bool,
+}
+
+impl StringLiteral {
+    // The decoded text of the literal, leaving out any interpolations
+    pub fn cooked(&self) -> String {
+        self.segments.iter()
+            .filter_map(|segment| match segment {
+                StringSegment::Literal { cooked, .. } => Some(cooked.as_str()),
+                StringSegment::Interpolation { .. } => None,
+            })
+            .collect()
+    }
+
+    // Raw strings only treat escapes followed by their delimiter's `#` as escapes
+    pub fn is_raw(&self) -> bool {
+        self.delimiter_depth > 0
+    }
+
+    pub fn is_interpolated(&self) -> bool {
+        self.segments.iter().any(|segment| matches!(segment, StringSegment::Interpolation { .. }))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegexLiteral {
+    pub delimiter_depth: u64, // Number of `#` around the slashes of an extended literal
+    pub multiline: bool,
+}
+
+// Byte offsets of a token in its source, the end being exclusive
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl Span {
+    pub fn new(start: u64, end: u64) -> Span {
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Space,
+    Tab,
+    Whitespace, // Any other horizontal whitespace, such as a form feed
+    Newline,
+    LineComment,
+    BlockComment,
+    DocLineComment,
+    DocBlockComment,
+}
+
+// Source text between tokens that does not affect the meaning of the program
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub text: String,
+}
+
+impl Trivia {
+    pub fn new(kind: TriviaKind, text: String) -> Trivia {
+        Trivia { kind, text }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub value: String,
     pub file: String,
     pub line: u64,
-    pub col: u64,
+    pub col: u64, // In characters from the start of the line
+    pub span: Span,
+    pub fixity: Option<Fixity>,
+    pub radix: Option<Radix>,
+    pub string: Option<StringLiteral>,
+    pub regex: Option<RegexLiteral>,
+    pub contextual_keyword: Option<ContextualKeyword>, // Set on identifiers that may act as a keyword
+    // Only kept when tokenizing with trivia, together they hold every byte of the source
+    pub text: String,
+    pub leading_trivia: Vec<Trivia>,
+    pub trailing_trivia: Vec<Trivia>,
 }
 
 impl Token {
@@ -203,7 +586,31 @@ impl Token {
             value,
             file,
             line,
-            col
+            col,
+            span: Span::default(),
+            fixity: None,
+            radix: None,
+            string: None,
+            regex: None,
+            contextual_keyword: None,
+            text: String::new(),
+            leading_trivia: vec![],
+            trailing_trivia: vec![],
+        }
+    }
+}
+
+// Rebuilds the source of tokens lexed with trivia
+pub fn tokens_to_source(tokens: &[Token]) -> String {
+    let mut source = String::new();
+    for token in tokens {
+        for trivia in &token.leading_trivia {
+            source.push_str(&trivia.text);
+        }
+        source.push_str(&token.text);
+        for trivia in &token.trailing_trivia {
+            source.push_str(&trivia.text);
         }
     }
+    source
 }
\ No newline at end of file
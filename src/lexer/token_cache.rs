@@ -0,0 +1,424 @@
+use std::{collections::HashMap, io::{Read, Write}};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use serde::de::{value::{Error, U32Deserializer}, DeserializeOwned};
+
+use super::{diagnostic::Diagnostic, token::{RegexLiteral, Span, StringLiteral, StringSegment, Token, TokenKind, Trivia}};
+
+// First byte of an encoding, telling whether the rest of it is deflated
+const PLAIN: u8 = 0;
+const DEFLATED: u8 = 1;
+
+// Kinds are stored in a byte as their discriminant less one, `ANYTHING` being the last of them
+const _: () = assert!(TokenKind::ANYTHING as u64 - 1 <= u8::MAX as u64, "token kinds no longer fit in a byte");
+
+// Encodes tokens compactly for the cache, every string is stored once in a table and referred to by its index,
+// numbers are varints, enums a single byte holding their variant and the optional parts of a token are flagged
+pub fn encode_tokens(tokens: &[Token], diagnostics: &[Diagnostic], compress: bool) -> Vec<u8> {
+    let mut encoder = Encoder::default();
+    encoder.varint(diagnostics.len() as u64);
+    for diagnostic in diagnostics {
+        encoder.string(&diagnostic.message);
+        encoder.string(&diagnostic.file);
+        encoder.varint(diagnostic.line);
+        encoder.varint(diagnostic.col);
+    }
+    encoder.tokens(tokens);
+
+    let mut body = vec![];
+    write_varint(&mut body, encoder.strings.len() as u64);
+    for string in &encoder.strings {
+        write_varint(&mut body, string.len() as u64);
+        body.extend_from_slice(string.as_bytes());
+    }
+    body.append(&mut encoder.bytes);
+
+    if !compress {
+        body.insert(0, PLAIN);
+        return body;
+    }
+    let mut deflater = DeflateEncoder::new(vec![DEFLATED], Compression::fast());
+    deflater.write_all(&body).expect("Writing to memory does not fail");
+    deflater.finish().expect("Writing to memory does not fail")
+}
+
+// Decodes tokens written by `encode_tokens`, giving nothing back for data that is damaged
+pub fn decode_tokens(bytes: &[u8]) -> Option<(Vec<Token>, Vec<Diagnostic>)> {
+    let inflated;
+    let body = match bytes.split_first()? {
+        (&PLAIN, body) => body,
+        (&DEFLATED, body) => {
+            let mut buffer = vec![];
+            DeflateDecoder::new(body).read_to_end(&mut buffer).ok()?;
+            inflated = buffer;
+            &inflated
+        },
+        _ => return None,
+    };
+
+    let mut decoder = Decoder { bytes: body, ..Default::default() };
+    let string_count = decoder.varint()?;
+    for _ in 0..string_count {
+        let len = decoder.varint()? as usize;
+        let string = String::from_utf8(decoder.take(len)?.to_vec()).ok()?;
+        decoder.strings.push(string);
+    }
+
+    let diagnostic_count = decoder.varint()?;
+    let mut diagnostics = vec![];
+    for _ in 0..diagnostic_count {
+        diagnostics.push(Diagnostic::new(decoder.string()?, decoder.string()?, decoder.varint()?, decoder.varint()?));
+    }
+    let tokens = decoder.tokens()?;
+    if !decoder.bytes.is_empty() {
+        return None;
+    }
+    Some((tokens, diagnostics))
+}
+
+// Bits of the flags starting each token, telling which of its optional parts follow. The rarer ones come last
+// so the flags of most tokens fit in a single varint byte
+const FIXITY: u64 = 1 << 0;
+const RADIX: u64 = 1 << 1;
+const CONTEXTUAL_KEYWORD: u64 = 1 << 2;
+const STRING: u64 = 1 << 3;
+const TEXT: u64 = 1 << 4;
+const LEADING_TRIVIA: u64 = 1 << 5;
+const TRAILING_TRIVIA: u64 = 1 << 6;
+const REGEX: u64 = 1 << 7;
+const NEW_FILE: u64 = 1 << 8;
+
+// Positions are stored relative to those of the token before, which mostly keeps them to a byte
+#[derive(Default)]
+struct Position<F> {
+    file: F,
+    line: u64,
+    col: u64,
+    end: u64,
+}
+
+#[derive(Default)]
+struct Encoder<'a> {
+    bytes: Vec<u8>,
+    strings: Vec<&'a str>,
+    indices: HashMap<&'a str, u64>,
+    last: Position<&'a str>,
+}
+
+impl<'a> Encoder<'a> {
+    fn varint(&mut self, value: u64) {
+        write_varint(&mut self.bytes, value);
+    }
+
+    // Signed differences are zigzag encoded so small ones either way stay small
+    fn delta(&mut self, value: u64, from: u64) {
+        let delta = value.wrapping_sub(from) as i64;
+        self.varint(((delta << 1) ^ (delta >> 63)) as u64);
+    }
+
+    fn string(&mut self, string: &'a str) {
+        let index = *self.indices.entry(string).or_insert_with(|| {
+            self.strings.push(string);
+            self.strings.len() as u64 - 1
+        });
+        self.varint(index);
+    }
+
+    fn tokens(&mut self, tokens: &'a [Token]) {
+        self.varint(tokens.len() as u64);
+        for token in tokens {
+            self.token(token);
+        }
+    }
+
+    fn token(&mut self, token: &'a Token) {
+        let flags = [
+            (FIXITY, token.fixity.is_some()),
+            (RADIX, token.radix.is_some()),
+            (CONTEXTUAL_KEYWORD, token.contextual_keyword.is_some()),
+            (STRING, token.string.is_some()),
+            (TEXT, !token.text.is_empty()),
+            (LEADING_TRIVIA, !token.leading_trivia.is_empty()),
+            (TRAILING_TRIVIA, !token.trailing_trivia.is_empty()),
+            (REGEX, token.regex.is_some()),
+            (NEW_FILE, token.file != self.last.file),
+        ];
+        // Token kinds start at one
+        self.bytes.push((token.kind as u64 - 1) as u8);
+        self.varint(flags.iter().filter(|(_, set)| *set).fold(0, |flags, (flag, _)| flags | flag));
+        self.string(&token.value);
+        if token.file != self.last.file {
+            self.string(&token.file);
+        }
+        self.delta(token.line, self.last.line);
+        match token.line == self.last.line {
+            true => self.delta(token.col, self.last.col),
+            false => self.varint(token.col),
+        }
+        self.delta(token.span.start, self.last.end);
+        self.varint(token.span.end - token.span.start);
+        self.last = Position { file: &token.file, line: token.line, col: token.col, end: token.span.end };
+
+        if let Some(fixity) = token.fixity {
+            self.bytes.push(fixity as u8);
+        }
+        if let Some(radix) = token.radix {
+            self.bytes.push(radix as u8);
+        }
+        if let Some(keyword) = token.contextual_keyword {
+            self.bytes.push(keyword as u8);
+        }
+        if let Some(regex) = &token.regex {
+            self.bytes.push(regex.multiline as u8);
+            self.varint(regex.delimiter_depth);
+        }
+        if let Some(string) = &token.string {
+            self.bytes.push(string.multiline as u8);
+            self.varint(string.delimiter_depth);
+            self.varint(string.segments.len() as u64);
+            for segment in &string.segments {
+                match segment {
+                    StringSegment::Literal { value, cooked } => {
+                        self.bytes.push(0);
+                        self.string(value);
+                        self.string(cooked);
+                    },
+                    StringSegment::Interpolation { tokens } => {
+                        self.bytes.push(1);
+                        self.tokens(tokens);
+                    },
+                }
+            }
+        }
+        if !token.text.is_empty() {
+            self.string(&token.text);
+        }
+        if !token.leading_trivia.is_empty() {
+            self.trivia(&token.leading_trivia);
+        }
+        if !token.trailing_trivia.is_empty() {
+            self.trivia(&token.trailing_trivia);
+        }
+    }
+
+    fn trivia(&mut self, trivia: &'a [Trivia]) {
+        self.varint(trivia.len() as u64);
+        for piece in trivia {
+            self.bytes.push(piece.kind as u8);
+            self.string(&piece.text);
+        }
+    }
+}
+
+#[derive(Default)]
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    strings: Vec<String>,
+    last: Position<String>,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.bytes.len() {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn delta(&mut self, from: u64) -> Option<u64> {
+        let zigzag = self.varint()?;
+        let delta = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+        Some(from.wrapping_add(delta as u64))
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let index = self.varint()? as usize;
+        self.strings.get(index).cloned()
+    }
+
+    fn flag(&mut self) -> Option<bool> {
+        match self.byte()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+
+    fn variant<T: DeserializeOwned>(&mut self) -> Option<T> {
+        variant_at(self.byte()? as u32)
+    }
+
+    fn tokens(&mut self) -> Option<Vec<Token>> {
+        let count = self.varint()?;
+        let mut tokens = vec![];
+        for _ in 0..count {
+            tokens.push(self.token()?);
+        }
+        Some(tokens)
+    }
+
+    fn token(&mut self) -> Option<Token> {
+        let kind = self.variant()?;
+        let flags = self.varint()?;
+        if flags >= NEW_FILE << 1 {
+            return None;
+        }
+        let value = self.string()?;
+        let file = match flags & NEW_FILE {
+            0 => self.last.file.clone(),
+            _ => self.string()?,
+        };
+        let line = self.delta(self.last.line)?;
+        let col = match line == self.last.line {
+            true => self.delta(self.last.col)?,
+            false => self.varint()?,
+        };
+        let mut token = Token::new(kind, value, file, line, col);
+        let start = self.delta(self.last.end)?;
+        token.span = Span::new(start, start.checked_add(self.varint()?)?);
+        self.last = Position { file: token.file.clone(), line, col, end: token.span.end };
+
+        if flags & FIXITY != 0 {
+            token.fixity = Some(self.variant()?);
+        }
+        if flags & RADIX != 0 {
+            token.radix = Some(self.variant()?);
+        }
+        if flags & CONTEXTUAL_KEYWORD != 0 {
+            token.contextual_keyword = Some(self.variant()?);
+        }
+        if flags & REGEX != 0 {
+            token.regex = Some(RegexLiteral { multiline: self.flag()?, delimiter_depth: self.varint()? });
+        }
+        if flags & STRING != 0 {
+            let multiline = self.flag()?;
+            let delimiter_depth = self.varint()?;
+            let count = self.varint()?;
+            let mut segments = vec![];
+            for _ in 0..count {
+                segments.push(match self.flag()? {
+                    false => StringSegment::Literal { value: self.string()?, cooked: self.string()? },
+                    true => StringSegment::Interpolation { tokens: self.tokens()? },
+                });
+            }
+            token.string = Some(StringLiteral { segments, delimiter_depth, multiline });
+        }
+        if flags & TEXT != 0 {
+            token.text = self.string()?;
+        }
+        if flags & LEADING_TRIVIA != 0 {
+            token.leading_trivia = self.trivia()?;
+        }
+        if flags & TRAILING_TRIVIA != 0 {
+            token.trailing_trivia = self.trivia()?;
+        }
+        Some(token)
+    }
+
+    fn trivia(&mut self) -> Option<Vec<Trivia>> {
+        let count = self.varint()?;
+        let mut trivia = vec![];
+        for _ in 0..count {
+            let kind = self.variant()?;
+            trivia.push(Trivia::new(kind, self.string()?));
+        }
+        Some(trivia)
+    }
+}
+
+pub(crate) fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+// Looks an enum variant up by its position in the enum's declaration, which is how the encoding stores it
+fn variant_at<T: DeserializeOwned>(index: u32) -> Option<T> {
+    T::deserialize(U32Deserializer::<Error>::new(index)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{token::{ContextualKeyword, Fixity, Radix, TriviaKind}, tokenizer};
+
+    // Every variant serde knows of must be stored as the byte that serde reads back as that same variant
+    fn assert_variants_round_trip<T: DeserializeOwned + Copy + std::fmt::Debug>(stored_as: fn(T) -> u64) -> u32 {
+        let mut count = 0;
+        while let Some(variant) = variant_at::<T>(count) {
+            assert_eq!(stored_as(variant), count as u64, "{:?} is stored as another variant", variant);
+            count += 1;
+        }
+        count
+    }
+
+    #[test]
+    fn every_token_kind_round_trips() {
+        let count = assert_variants_round_trip::<TokenKind>(|kind| kind as u64 - 1);
+        assert_eq!(count as u64, TokenKind::ANYTHING as u64);
+        for index in 0..count {
+            let kind = variant_at(index).unwrap();
+            let token = Token::new(kind, String::from("value"), String::from("test.swift"), 1, 1);
+            let (tokens, _) = decode_tokens(&encode_tokens(&[token], &[], false)).unwrap();
+            assert_eq!(tokens[0].kind, kind);
+        }
+    }
+
+    #[test]
+    fn every_enum_variant_round_trips() {
+        assert_variants_round_trip::<Fixity>(|fixity| fixity as u64);
+        assert_variants_round_trip::<Radix>(|radix| radix as u64);
+        assert_variants_round_trip::<ContextualKeyword>(|keyword| keyword as u64);
+        assert_variants_round_trip::<TriviaKind>(|kind| kind as u64);
+    }
+
+    #[test]
+    fn tokens_round_trip() {
+        let source = "/// Doc\n@available(*, deprecated) func f(x: inout Int) {\n\tlet s = #\"a\\#(x &+ 0x1F)\"# + /[a-z]+/ // c\n}\n\"\\q";
+        for compress in [false, true] {
+            let (tokens, diagnostics) = tokenizer::tokenize_with_trivia(source, String::from("test.swift"));
+            let decoded = decode_tokens(&encode_tokens(&tokens, &diagnostics, compress)).unwrap();
+            assert!(!diagnostics.is_empty());
+            assert_eq!(serde_json::to_string(&decoded).unwrap(), serde_json::to_string(&(tokens, diagnostics)).unwrap());
+        }
+    }
+
+    #[test]
+    fn positions_round_trip_in_any_order() {
+        let (mut tokens, _) = tokenizer::tokenize("let a = 1\nlet b = a", String::from("a.swift"));
+        let (mut others, _) = tokenizer::tokenize("f(\n  x)", String::from("b.swift"));
+        tokens.append(&mut others);
+        tokens.reverse();
+        let (decoded, _) = decode_tokens(&encode_tokens(&tokens, &[], false)).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), serde_json::to_string(&tokens).unwrap());
+    }
+
+    #[test]
+    fn damaged_encoding_is_rejected() {
+        let (tokens, diagnostics) = tokenizer::tokenize("let a = \"\\(b)\"", String::from("test.swift"));
+        let encoded = encode_tokens(&tokens, &diagnostics, false);
+        for len in 0..encoded.len() {
+            assert!(decode_tokens(&encoded[..len]).is_none());
+        }
+        assert!(decode_tokens(&[DEFLATED, 1, 2, 3]).is_none());
+    }
+}
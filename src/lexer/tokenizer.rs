@@ -1,46 +1,55 @@
-use regex::Regex;
-use super::token::{string_to_token, Token, TokenKind};
-
-macro_rules! token_pattern {
-    ($pattern:expr, $kind:expr, $value:expr) => {
-        RegexPattern::new(
-            Regex::new($pattern).unwrap(),
-            |lex, _| default_handler(lex, $kind, $value)
-        )
-    };
-}
-
-type RegexHandler = fn (&mut Lexer, &Regex);
-#[derive(Clone)]
-struct RegexPattern {
-    regex: Regex,
-    handler: RegexHandler,
-}
-
-impl RegexPattern {
-    fn new(regex: Regex, handler: RegexHandler) -> RegexPattern {
-        RegexPattern { regex, handler }
-    }
-}
+use super::{diagnostic::Diagnostic, token::{operator_to_token, string_to_contextual_keyword, string_to_token, Fixity, Radix, RegexLiteral, Span, StringLiteral, StringSegment, Token, TokenKind, Trivia, TriviaKind}};
 
 struct Lexer<'a> {
-    patterns: Vec<RegexPattern>,
     tokens: Vec<Token>,
+    diagnostics: Vec<Diagnostic>,
     source: &'a str,
     source_name: String,
     pos: u64,
     line: u64,
     col: u64,
+    interpolation_depth: Option<u64>, // Open parentheses inside of the string interpolation being lexed, if any
+    ends_at_line_break: bool, // Set for interpolations of single-line strings, which can not go past their line
+    finished: bool,
+    keep_trivia: bool,
+    pending_trivia: Vec<Trivia>, // Trivia to lead the next token
+    after_line_break: bool, // Whether a line break was passed since the last token
 }
 
 impl<'a> Lexer<'a> {
-    fn new(pos: u64, line: u64, col: u64, source: &'a str, source_name: String, patterns: Vec<RegexPattern>) -> Lexer<'a> {
-        Lexer { patterns, tokens: vec![], source, source_name, pos, line, col }
+    fn new(pos: u64, line: u64, col: u64, source: &'a str, source_name: String) -> Lexer<'a> {
+        Lexer { tokens: vec![], diagnostics: vec![], source, source_name, pos, line, col, interpolation_depth: None, ends_at_line_break: false, finished: false, keep_trivia: false, pending_trivia: vec![], after_line_break: false }
+    }
+
+    // Lexes until the end of the source, or until the parenthesis closing the interpolation being lexed
+    fn run(&mut self) {
+        while !self.at_eof() && !self.finished {
+            if self.ends_at_line_break && line_break_len(self.remainder()) > 0 {
+                break;
+            }
+            let (count, start) = (self.tokens.len(), self.pos);
+            lex_token(self);
+            for token in &mut self.tokens[count..] {
+                token.span = Span::new(start, self.pos);
+                if self.keep_trivia {
+                    token.text = self.source[start as usize..self.pos as usize].to_owned();
+                }
+            }
+        }
     }
 
+    // Moves `n` bytes forward on the current line, columns counting characters rather than bytes
     fn advance_n(&mut self, n: u64) {
+        let skipped = &self.source[self.pos as usize..(self.pos + n) as usize];
         self.pos += n;
-        self.col += n;
+        self.col += skipped.chars().count() as u64;
+    }
+
+    // Moves past a line break of `len` bytes onto the start of the next line
+    fn new_line(&mut self, len: usize) {
+        self.pos += len as u64;
+        self.line += 1;
+        self.col = 1;
     }
 
     fn at_eof(&mut self) -> bool {
@@ -51,152 +60,1026 @@ impl<'a> Lexer<'a> {
         &self.source[self.pos as usize..]
     }
 
-    fn push(&mut self, token: Token) {
+    // Whether the character before the current position binds an operator starting here to its left operand
+    fn is_left_bound(&self) -> bool {
+        let before = &self.source[..self.pos as usize];
+        match before.chars().next_back() {
+            None => false,
+            Some(' ' | '\r' | '\n' | '\t' | '(' | '[' | '{' | ',' | ';' | ':') => false,
+            Some('/') => !before.ends_with("*/"),
+            Some(_) => true,
+        }
+    }
+
+    // Whether the character after an operator of `len` bytes binds it to its right operand
+    fn is_right_bound(&self, len: usize, left_bound: bool) -> bool {
+        let after = &self.source[self.pos as usize + len..];
+        match after.chars().next() {
+            None => false,
+            Some(' ' | '\r' | '\n' | '\t' | ')' | ']' | '}' | ',' | ';' | ':') => false,
+            Some('.') => !left_bound,
+            Some('/') => !after.starts_with("//") && !after.starts_with("/*"),
+            Some(_) => true,
+        }
+    }
+
+    fn push(&mut self, mut token: Token) {
+        token.leading_trivia = std::mem::take(&mut self.pending_trivia);
         self.tokens.push(token);
+        self.after_line_break = false;
+    }
+
+    // Keeps trivia when lexing losslessly, and doc comments always. Trivia on the same line as the previous token
+    // trails it, anything from the first line break onward leads the next token
+    fn trivia(&mut self, kind: TriviaKind, text: &str) {
+        if kind == TriviaKind::Newline {
+            self.after_line_break = true;
+        }
+        if !self.keep_trivia && kind != TriviaKind::DocLineComment && kind != TriviaKind::DocBlockComment {
+            return;
+        }
+        let trivia = Trivia::new(kind, text.to_string());
+        match self.tokens.last_mut() {
+            Some(last) if !self.after_line_break => last.trailing_trivia.push(trivia),
+            _ => self.pending_trivia.push(trivia),
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        self.error_at(message, self.line, self.col);
+    }
+
+    fn error_at(&mut self, message: String, line: u64, col: u64) {
+        self.diagnostics.push(Diagnostic::new(message, self.source_name.clone(), line, col));
+    }
+
+    // Consumes a single unrecognized character, growing the previous ERROR token when it is directly adjacent
+    fn unrecognized(&mut self) {
+        let len = self.remainder().chars().next().map_or(1, |c| c.len_utf8());
+        let value = &self.source[self.pos as usize..self.pos as usize + len];
+        let span = Span::new(self.pos, self.pos + len as u64);
+        if let Some(last) = self.tokens.last_mut() {
+            if last.kind == TokenKind::ERROR && last.span.end == span.start && last.trailing_trivia.is_empty() {
+                last.value.push_str(value);
+                if self.keep_trivia {
+                    last.text.push_str(value);
+                }
+                last.span.end = span.end;
+                self.advance_n(len as u64);
+                return;
+            }
+        }
+
+        self.error(format!("unrecognized token near {:}", value));
+        let mut token = Token::new(TokenKind::ERROR, value.to_owned(), self.source_name.clone(), self.line, self.col);
+        token.span = span;
+        if self.keep_trivia {
+            token.text = value.to_owned();
+        }
+        self.push(token);
+        self.advance_n(len as u64);
+    }
+
+    fn push_eof(&mut self) {
+        let mut token = Token::new(TokenKind::EOF, String::from(""), self.source_name.clone(), self.line, self.col);
+        token.span = Span::new(self.pos, self.pos);
+        self.push(token);
+    }
+}
+
+pub fn tokenize(source: &str, source_name: String) -> (Vec<Token>, Vec<Diagnostic>) {
+    let mut lexer = Lexer::new(0, 1, 1, source, source_name);
+    lexer.run();
+    lexer.push_eof();
+
+    (lexer.tokens, lexer.diagnostics)
+}
+
+// Tokenizes losslessly, every token keeping its text and the trivia around it so the source can be rebuilt
+pub fn tokenize_with_trivia(source: &str, source_name: String) -> (Vec<Token>, Vec<Diagnostic>) {
+    let mut lexer = Lexer::new(0, 1, 1, source, source_name);
+    lexer.keep_trivia = true;
+    lexer.run();
+    lexer.push_eof();
+
+    (lexer.tokens, lexer.diagnostics)
+}
+
+// Length of the line break at the start of the text, `\r\n`, `\n` and a lone `\r` each being a single break
+fn line_break_len(text: &str) -> usize {
+    if text.starts_with("\r\n") {
+        2
+    } else if text.starts_with(['\r', '\n']) {
+        1
+    } else {
+        0
+    }
+}
+
+// Lexes whatever starts at the current position, the next character alone deciding which handler lexes it
+fn lex_token(lex: &mut Lexer) {
+    let rest = lex.remainder();
+    let Some(c) = rest.chars().next() else {
+        return;
+    };
+    let after = rest[c.len_utf8()..].chars().next();
+    let hashes = rest.bytes().take_while(|b| *b == b'#').count();
+    match c {
+        '\r' | '\n' => new_line_handler(lex),
+        _ if c.is_whitespace() => whitespace_handler(lex),
+        '/' if rest.starts_with("/*") => block_comment_handler(lex),
+        '/' if rest.starts_with("//") => line_comment_handler(lex),
+        '"' | '#' if rest[hashes..].starts_with("\"\"\"") => lex_string(lex, hashes, true),
+        '"' | '#' if rest[hashes..].starts_with('"') => lex_string(lex, hashes, false),
+        '#' if rest[hashes..].starts_with('/') => extended_regex_handler(lex, hashes),
+        '#' => macro_handler(lex),
+        '0'..='9' => number_handler(lex),
+        '-' if after.is_some_and(|after| after.is_ascii_digit()) => number_handler(lex),
+        '`' if after.is_some_and(is_identifier_head) => symbol_handler(lex),
+        _ if is_identifier_head(c) => symbol_handler(lex),
+        '$' if after.is_some_and(|after| after.is_ascii_digit() || is_identifier_head(after)) => dollar_identifier_handler(lex),
+        '@' => annotation_handler(lex),
+        '[' => default_handler(lex, TokenKind::OPEN_BRACKET, "["),
+        ']' => default_handler(lex, TokenKind::CLOSE_BRACKET, "]"),
+        '{' => default_handler(lex, TokenKind::OPEN_BRACE, "{"),
+        '}' => default_handler(lex, TokenKind::CLOSE_BRACE, "}"),
+        '(' => default_handler(lex, TokenKind::OPEN_PAREN, "("),
+        ')' => default_handler(lex, TokenKind::CLOSE_PAREN, ")"),
+        ':' => default_handler(lex, TokenKind::COLON, ":"),
+        ';' => default_handler(lex, TokenKind::SEMI_COLON, ";"),
+        ',' => default_handler(lex, TokenKind::COMMA, ","),
+        '\\' => default_handler(lex, TokenKind::BACKSLASH, "\\"),
+        '.' => operator_handler(lex),
+        _ if is_operator_head(c) => operator_handler(lex),
+        _ => lex.unrecognized(),
+    }
+}
+
+// Characters an identifier may start with, following the Swift identifier-head grammar
+fn is_identifier_head(c: char) -> bool {
+    match c {
+        'a'..='z' | 'A'..='Z' | '_' | '\u{A8}' | '\u{AA}' | '\u{AD}' | '\u{AF}' | '\u{B2}'..='\u{B5}' |
+        '\u{B7}'..='\u{BA}' | '\u{BC}'..='\u{BE}' | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{FF}' |
+        '\u{100}'..='\u{2FF}' | '\u{370}'..='\u{167F}' | '\u{1681}'..='\u{180D}' | '\u{180F}'..='\u{1DBF}' |
+        '\u{1E00}'..='\u{1FFF}' | '\u{200B}'..='\u{200D}' | '\u{202A}'..='\u{202E}' | '\u{203F}' | '\u{2040}' |
+        '\u{2054}' | '\u{2060}'..='\u{206F}' | '\u{2070}'..='\u{20CF}' | '\u{2100}'..='\u{218F}' |
+        '\u{2460}'..='\u{24FF}' | '\u{2776}'..='\u{2793}' | '\u{2C00}'..='\u{2DFF}' | '\u{2E80}'..='\u{2FFF}' |
+        '\u{3004}'..='\u{3007}' | '\u{3021}'..='\u{302F}' | '\u{3031}'..='\u{303F}' | '\u{3040}'..='\u{D7FF}' |
+        '\u{F900}'..='\u{FD3D}' | '\u{FD40}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FE1F}' | '\u{FE30}'..='\u{FE44}' |
+        '\u{FE47}'..='\u{FFFD}' => true,
+        // Every supplementary plane up to the fourteenth, except for the last two code points of each
+        '\u{10000}'..='\u{EFFFD}' => (c as u32 & 0xFFFF) <= 0xFFFD,
+        _ => false,
+    }
+}
+
+// Characters an identifier may continue with
+fn is_identifier_character(c: char) -> bool {
+    match c {
+        '0'..='9' | '\u{300}'..='\u{36F}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}' => true,
+        _ => is_identifier_head(c),
     }
+}
+
+// Characters an operator may start with, following the Swift operator-head grammar
+fn is_operator_head(c: char) -> bool {
+    matches!(c,
+        '/' | '=' | '-' | '+' | '!' | '*' | '%' | '<' | '>' | '&' | '|' | '^' | '~' | '?' | '\u{A1}'..='\u{A7}' |
+        '\u{A9}' | '\u{AB}' | '\u{AC}' | '\u{AE}' | '\u{B0}' | '\u{B1}' | '\u{B6}' | '\u{BB}' | '\u{BF}' | '\u{D7}' |
+        '\u{F7}' | '\u{2016}' | '\u{2017}' | '\u{2020}'..='\u{2027}' | '\u{2030}'..='\u{203E}' |
+        '\u{2041}'..='\u{2053}' | '\u{2055}'..='\u{205E}' | '\u{2190}'..='\u{23FF}' | '\u{2500}'..='\u{2775}' |
+        '\u{2794}'..='\u{2BFF}' | '\u{2E00}'..='\u{2E7F}' | '\u{3001}'..='\u{3003}' | '\u{3008}'..='\u{3020}' | '\u{3030}'
+    )
+}
+
+// Characters an operator may continue with, the combining characters besides any operator head
+fn is_operator_character(c: char) -> bool {
+    match c {
+        '\u{300}'..='\u{36F}' | '\u{1DC0}'..='\u{1DFF}' | '\u{20D0}'..='\u{20FF}' | '\u{FE00}'..='\u{FE0F}' |
+        '\u{FE20}'..='\u{FE2F}' | '\u{E0100}'..='\u{E01EF}' => true,
+        _ => is_operator_head(c),
+    }
+}
 
-    fn error_dump(&mut self) -> String {
-        format!("{}:{}:{}: error:", self.source_name, self.line, self.col)
+// Byte length of the longest prefix of the text made of characters accepted by `accept`
+fn prefix_len(text: &str, accept: impl Fn(char) -> bool) -> usize {
+    text.find(|c: char| !accept(c)).unwrap_or(text.len())
+}
+
+// Length of an identifier at the start of the text, zero when it doesn't start with one
+fn identifier_len(text: &str) -> usize {
+    match text.chars().next() {
+        Some(c) if is_identifier_head(c) => c.len_utf8() + prefix_len(&text[c.len_utf8()..], is_identifier_character),
+        _ => 0,
     }
 }
 
-pub fn tokenize(source: &str, source_name: String) -> Vec<Token> {
-    let mut lexer = create_lexer(source, source_name.clone());
-    let patterns = lexer.patterns.clone();
+// Length of a run of digits at the start of the bytes that may be separated by underscores, but not start with one
+fn digits_len(bytes: &[u8], is_digit: fn(&u8) -> bool) -> usize {
+    match bytes.first() {
+        Some(first) if is_digit(first) => 1 + bytes[1..].iter().take_while(|b| is_digit(b) || **b == b'_').count(),
+        _ => 0,
+    }
+}
 
-    while !lexer.at_eof() {
-        let mut matched = false;
+// Length of a number literal at the start of the text, in the first of the hexadecimal float, hexadecimal,
+// octal, binary and decimal forms it can be read as
+fn number_len(text: &str) -> usize {
+    let sign = usize::from(text.starts_with('-'));
+    let bytes = &text.as_bytes()[sign..];
+    let at = |i: usize| bytes.get(i).copied().unwrap_or(0);
 
-        for pattern in &patterns {
-            if let Some(first_match) = pattern.regex.find(lexer.remainder()) {
-                if first_match.start() == 0 {
-                    (pattern.handler)(&mut lexer, &pattern.regex);
-                    matched = true;
-                    break;
+    if bytes.starts_with(b"0x") {
+        let integer = digits_len(&bytes[2..], u8::is_ascii_hexdigit);
+        if integer > 0 {
+            let mut end = 2 + integer;
+            if at(end) == b'.' {
+                let fraction = digits_len(&bytes[end + 1..], u8::is_ascii_hexdigit);
+                if fraction > 0 {
+                    end += 1 + fraction;
                 }
             }
+            if matches!(at(end), b'p' | b'P') {
+                let sign_len = usize::from(matches!(at(end + 1), b'+' | b'-'));
+                let exponent = digits_len(&bytes[end + 1 + sign_len..], u8::is_ascii_digit);
+                if exponent > 0 {
+                    return sign + end + 1 + sign_len + exponent;
+                }
+            }
+            return sign + 2 + integer;
         }
+    }
 
-        if !matched {
-            panic!("{:}, lexer error: unrecognized token near {:}", lexer.error_dump(), lexer.remainder());
-        }
-    }
-
-    lexer.push(Token::new(TokenKind::EOF, String::from(""), source_name.clone(), lexer.line, lexer.pos));
-
-    lexer.tokens
-}
-
-fn create_lexer(source: &str, source_name: String) -> Lexer {
-    let lexer = Lexer::new(0, 1, 1, source, source_name, vec![
-        RegexPattern::new(Regex::new(r"[^\S\r\n]+").unwrap(), skip_handler),
-        RegexPattern::new(Regex::new(r"[\n|\r|\r\n]").unwrap(), new_line_handler),
-        RegexPattern::new(Regex::new(r"\/\*[\s\S]*?\*\/").unwrap(), block_comment_handler),
-        RegexPattern::new(Regex::new(r"\/\/.*").unwrap(), skip_handler),
-        RegexPattern::new(Regex::new(r#""""[\s\S]+?""""#).unwrap(), block_string_handler),
-        RegexPattern::new(Regex::new(r#""([^"\\\r\n]*(\\.[^"\\\r\n]*)*)""#).unwrap(), string_handler),
-        RegexPattern::new(Regex::new(r"[0-9]+(\.[0-9]+)?").unwrap(), number_handler),
-        RegexPattern::new(Regex::new("[a-zA-Z_][a-zA-Z0-9_]*").unwrap(), symbol_handler),
-        RegexPattern::new(Regex::new(r"@[a-zA-Z0-9_]*").unwrap(), annotation_handler),
-        RegexPattern::new(Regex::new(r"#[a-zA-Z0-9_]*").unwrap(), macro_handler),
-        token_pattern!(r"\[", TokenKind::OPEN_BRACKET, "["),
-        token_pattern!(r"\]", TokenKind::CLOSE_BRACKET, "]"),
-        token_pattern!(r"\{", TokenKind::OPEN_BRACE, "{"),
-        token_pattern!(r"\}", TokenKind::CLOSE_BRACE, "}"),
-        token_pattern!(r"\(", TokenKind::OPEN_PAREN, "("),
-        token_pattern!(r"\)", TokenKind::CLOSE_PAREN, ")"),
-        token_pattern!(":", TokenKind::COLON, ":"),
-        token_pattern!(";", TokenKind::SEMI_COLON, ";"),
-        token_pattern!(r"\.\.\.", TokenKind::DOT_DOT_DOT, "..."),
-        token_pattern!(r"\.\.<", TokenKind::RANGE, "..<"),
-        token_pattern!(r"\.", TokenKind::DOT, "."),
-        token_pattern!(r"\?\?", TokenKind::DEFAULTING, "??"),
-        token_pattern!(r"\?", TokenKind::OPTIONAL, "?"),
-        token_pattern!(">=", TokenKind::GREATER_EQUALS, ">="),
-        token_pattern!("==", TokenKind::EQUALS, "=="),
-        token_pattern!(r"\!=", TokenKind::NOT_EQUALS, "!="),
-        token_pattern!("<=", TokenKind::LESS_EQUALS, "<="),
-        token_pattern!("=", TokenKind::ASSIGNMENT, "="),
-        token_pattern!("<", TokenKind::LESS, "<"),
-        token_pattern!(">", TokenKind::GREATER, ">"),
-        token_pattern!(r"\!", TokenKind::NOT, "!"),
-        token_pattern!(r"\*", TokenKind::STAR, "*"),
-        token_pattern!(",", TokenKind::COMMA, ","),
-        token_pattern!("&&", TokenKind::AND, "&&"),
-        token_pattern!(r"\|\|", TokenKind::OR, "||"),
-        token_pattern!(r"\+=", TokenKind::PLUS_EQUALS, "+="),
-        token_pattern!("-=", TokenKind::MINUS_EQUALS, "-="),
-        token_pattern!("%", TokenKind::PERCENT, "%"),
-    ]);
-    lexer
+    let prefixed = match bytes.get(..2) {
+        Some(b"0o") => digits_len(&bytes[2..], |b| matches!(b, b'0'..=b'7')),
+        Some(b"0b") => digits_len(&bytes[2..], |b| matches!(b, b'0' | b'1')),
+        _ => 0,
+    };
+    if prefixed > 0 {
+        return sign + 2 + prefixed;
+    }
+
+    let mut end = digits_len(bytes, u8::is_ascii_digit);
+    if end == 0 {
+        return 0;
+    }
+    if at(end) == b'.' {
+        let fraction = digits_len(&bytes[end + 1..], u8::is_ascii_digit);
+        if fraction > 0 {
+            end += 1 + fraction;
+        }
+    }
+    if matches!(at(end), b'e' | b'E') {
+        let sign_len = usize::from(matches!(at(end + 1), b'+' | b'-'));
+        let exponent = digits_len(&bytes[end + 1 + sign_len..], u8::is_ascii_digit);
+        if exponent > 0 {
+            end += 1 + sign_len + exponent;
+        }
+    }
+    sign + end
+}
+
+// Length of an operator at the start of the text, operators are read greedily and those starting with a dot
+// may contain further dots
+fn operator_len(text: &str) -> usize {
+    match text.chars().next() {
+        Some('.') => 1 + prefix_len(&text[1..], |c| c == '.' || is_operator_character(c)),
+        Some(c) if is_operator_head(c) => c.len_utf8() + prefix_len(&text[c.len_utf8()..], is_operator_character),
+        _ => 0,
+    }
 }
 
 fn default_handler<'a>(lex: &mut Lexer<'a>, kind: TokenKind, value: &'a str) {
+    if let Some(depth) = lex.interpolation_depth {
+        match kind {
+            TokenKind::OPEN_PAREN => lex.interpolation_depth = Some(depth + 1),
+            TokenKind::CLOSE_PAREN if depth == 0 => {
+                lex.advance_n(1);
+                lex.finished = true;
+                return;
+            },
+            TokenKind::CLOSE_PAREN => lex.interpolation_depth = Some(depth - 1),
+            _ => {}
+        }
+    }
     lex.push(Token::new(kind, value.to_string(), lex.source_name.clone(), lex.line, lex.col));
     lex.advance_n(value.len() as u64);
 }
 
-fn annotation_handler(lex: &mut Lexer, regex: &Regex) {
-    if let Some(first_match) = regex.find(lex.remainder()) {
-        lex.push(Token::new(TokenKind::ANNOTATION, first_match.as_str().to_owned(), lex.source_name.clone(), lex.line, lex.col));
-        lex.advance_n(first_match.len() as u64);
+fn dollar_identifier_handler(lex: &mut Lexer) {
+    let rest = lex.remainder();
+    let len = match rest[1..].starts_with(|c: char| c.is_ascii_digit()) {
+        true => 1 + prefix_len(&rest[1..], |c| c.is_ascii_digit()),
+        false => 1 + identifier_len(&rest[1..]),
+    };
+    lex.push(Token::new(TokenKind::DOLLAR_IDENTIFIER, rest[..len].to_owned(), lex.source_name.clone(), lex.line, lex.col));
+    lex.advance_n(len as u64);
+}
+
+fn annotation_handler(lex: &mut Lexer) {
+    let rest = lex.remainder();
+    let len = 1 + prefix_len(&rest[1..], is_identifier_character);
+    lex.push(Token::new(TokenKind::ANNOTATION, rest[..len].to_owned(), lex.source_name.clone(), lex.line, lex.col));
+    lex.advance_n(len as u64);
+}
+
+fn macro_handler(lex: &mut Lexer) {
+    let rest = lex.remainder();
+    let value = &rest[..1 + prefix_len(&rest[1..], |c| c.is_ascii_alphanumeric() || c == '_')];
+    let kind = match value {
+        "#if" => TokenKind::POUND_IF,
+        "#elseif" => TokenKind::POUND_ELSEIF,
+        "#else" => TokenKind::POUND_ELSE,
+        "#endif" => TokenKind::POUND_ENDIF,
+        _ => TokenKind::MACRO,
+    };
+    lex.push(Token::new(kind, value.to_owned(), lex.source_name.clone(), lex.line, lex.col));
+    lex.advance_n(value.len() as u64);
+}
+
+fn operator_handler(lex: &mut Lexer) {
+    let mut value = &lex.remainder()[..operator_len(lex.remainder())];
+    // A comment starting inside of an operator ends it
+    let comment = value.char_indices().skip(1).find(|(i, _)| value[*i..].starts_with("//") || value[*i..].starts_with("/*"));
+    if let Some((comment, _)) = comment {
+        value = &value[..comment];
+    }
+
+    // A slash in prefix position may start a regex literal instead of an operator
+    if value.starts_with('/') && !lex.is_left_bound() {
+        if let Some(len) = bare_regex_len(lex.remainder()) {
+            let pattern = &lex.remainder()[1..len - 1];
+            push_regex(lex, pattern, 0, false, lex.line, lex.col);
+            lex.advance_n(len as u64);
+            return;
+        }
+    }
+
+    if value == "*/" {
+        lex.error(String::from("unexpected end of block comment"));
+        lex.push(Token::new(TokenKind::ERROR, value.to_owned(), lex.source_name.clone(), lex.line, lex.col));
+        lex.advance_n(value.len() as u64);
+        return;
+    }
+
+    let left_bound = lex.is_left_bound();
+    let right_bound = lex.is_right_bound(value.len(), left_bound);
+    let kind = *operator_to_token(value).unwrap_or(&TokenKind::OPERATOR);
+    let mut token = Token::new(kind, value.to_owned(), lex.source_name.clone(), lex.line, lex.col);
+    token.fixity = match kind {
+        TokenKind::DOT |
+        TokenKind::ASSIGNMENT |
+        TokenKind::ARROW => None,
+        TokenKind::OPTIONAL if left_bound => Some(Fixity::Postfix),
+        TokenKind::OPTIONAL => Some(Fixity::Infix),
+        _ if left_bound == right_bound => Some(Fixity::Infix),
+        _ if left_bound => Some(Fixity::Postfix),
+        _ => Some(Fixity::Prefix),
+    };
+    lex.push(token);
+    lex.advance_n(value.len() as u64);
+}
+
+// Length of a `/.../` regex literal at the start of the text, none when Swift would read the slash as division
+fn bare_regex_len(text: &str) -> Option<usize> {
+    let content = &text[1..];
+    if content.starts_with([' ', '\t']) {
+        return None;
+    }
+
+    let mut class_depth = 0;
+    let mut paren_depth = 0;
+    let mut chars = content.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if matches!(chars.next(), None | Some((_, '\r' | '\n'))) {
+                    return None;
+                }
+            },
+            '\r' | '\n' => return None,
+            '[' => class_depth += 1,
+            ']' if class_depth > 0 => class_depth -= 1,
+            '(' if class_depth == 0 => paren_depth += 1,
+            // An unbalanced parenthesis means the slashes are divisions inside of an argument list
+            ')' if class_depth == 0 && paren_depth == 0 => return None,
+            ')' if class_depth == 0 => paren_depth -= 1,
+            '/' if class_depth == 0 => {
+                if content[..i].ends_with([' ', '\t']) {
+                    return None;
+                }
+                return Some(i + 2);
+            },
+            _ => {}
+        }
     }
+    None
 }
 
-fn macro_handler(lex: &mut Lexer, regex: &Regex) {
-    if let Some(first_match) = regex.find(lex.remainder()) {
-        lex.push(Token::new(TokenKind::MACRO, first_match.as_str().to_owned(), lex.source_name.clone(), lex.line, lex.col));
-        lex.advance_n(first_match.len() as u64);
+fn extended_regex_handler(lex: &mut Lexer, depth: usize) {
+    let (line, col) = (lex.line, lex.col);
+    let closing = format!("/{}", "#".repeat(depth));
+    let opening = lex.pos as usize;
+    lex.advance_n(depth as u64 + 1);
+    // Only a literal whose opening delimiter ends its line may span several lines
+    let multiline = line_break_len(lex.remainder()) > 0;
+
+    let start = lex.pos as usize;
+    loop {
+        let rest = lex.remainder();
+        if rest.starts_with(&closing) {
+            break;
+        }
+
+        let len = match rest.chars().next() {
+            Some('\\') => 1 + rest[1..].chars().next().filter(|c| !matches!(c, '\r' | '\n')).map_or(0, |c| c.len_utf8()),
+            Some(c) => c.len_utf8(),
+            None => 0,
+        };
+        if line_break_len(rest) > 0 && multiline {
+            lex.new_line(line_break_len(rest));
+        } else if len == 0 || line_break_len(rest) > 0 {
+            lex.error_at(String::from("unterminated regex literal"), line, col);
+            let value = lex.source[opening..lex.pos as usize].to_owned();
+            lex.push(Token::new(TokenKind::ERROR, value, lex.source_name.clone(), line, col));
+            return;
+        } else {
+            lex.advance_n(len as u64);
+        }
     }
+
+    let pattern = &lex.source[start..lex.pos as usize];
+    push_regex(lex, pattern, depth as u64, multiline, line, col);
+    lex.advance_n(closing.len() as u64);
+}
+
+fn push_regex(lex: &mut Lexer, pattern: &str, delimiter_depth: u64, multiline: bool, line: u64, col: u64) {
+    let mut token = Token::new(TokenKind::REGEX, pattern.to_owned(), lex.source_name.clone(), line, col);
+    token.regex = Some(RegexLiteral { delimiter_depth, multiline });
+    lex.push(token);
 }
 
-fn symbol_handler(lex: &mut Lexer, regex: &Regex) {
-    if let Some(first_match) = regex.find(lex.remainder()) {
-        let token = string_to_token(first_match.as_str());
-        lex.push(Token::new(token.clone(), first_match.as_str().to_owned(), lex.source_name.clone(), lex.line, lex.col));
-        lex.advance_n(first_match.len() as u64);
+// Lexes an identifier or keyword, identifiers may be escaped with backticks to use a keyword as a name
+fn symbol_handler(lex: &mut Lexer) {
+    let rest = lex.remainder();
+    if let Some(escaped) = rest.strip_prefix('`') {
+        let name = &escaped[..identifier_len(escaped)];
+        if !escaped[name.len()..].starts_with('`') {
+            // Without its closing backtick only the opening one is in error
+            lex.error(String::from("expected '`' to end escaped identifier"));
+            lex.push(Token::new(TokenKind::ERROR, String::from("`"), lex.source_name.clone(), lex.line, lex.col));
+            lex.advance_n(1);
+            return;
+        }
+        // An escaped keyword is always a plain identifier
+        lex.push(Token::new(TokenKind::IDENTIFIER, name.to_owned(), lex.source_name.clone(), lex.line, lex.col));
+        lex.advance_n(name.len() as u64 + 2);
+        return;
+    }
+
+    let value = &rest[..identifier_len(rest)];
+    let kind = *string_to_token(value);
+    let mut token = Token::new(kind, value.to_owned(), lex.source_name.clone(), lex.line, lex.col);
+    if kind == TokenKind::IDENTIFIER {
+        token.contextual_keyword = string_to_contextual_keyword(value);
     }
+    lex.push(token);
+    lex.advance_n(value.len() as u64);
 }
 
-fn number_handler(lex: &mut Lexer, regex: &Regex) {
-    if let Some(first_match) = regex.find(lex.remainder()) {
-        lex.push(Token::new(TokenKind::NUMBER, first_match.as_str().to_owned(), lex.source_name.clone(), lex.line, lex.col));
-        lex.advance_n(first_match.len() as u64);
+fn number_handler(lex: &mut Lexer) {
+    // A number after a member access dot is a tuple element, so `t.0.1` reads its elements one at a time, while
+    // a number after a range operator such as `0...1.5` is still a whole literal
+    let before = &lex.source[..lex.pos as usize];
+    let tuple_element = before.ends_with('.') && !before.ends_with("..") && !lex.remainder().starts_with('-');
+    let len = match tuple_element {
+        true => digits_len(lex.remainder().as_bytes(), u8::is_ascii_digit),
+        false => number_len(lex.remainder()),
+    };
+    let mut value = &lex.remainder()[..len];
+    // A minus bound to its left is a binary operator rather than the sign of a negative literal
+    if value.starts_with('-') && lex.is_left_bound() {
+        let mut token = Token::new(TokenKind::MINUS, String::from("-"), lex.source_name.clone(), lex.line, lex.col);
+        token.fixity = Some(Fixity::Infix);
+        lex.push(token);
+        lex.advance_n(1);
+        return;
     }
+
+    let digits = value.trim_start_matches('-');
+    let radix = match digits.get(..2) {
+        Some("0x") => Radix::Hexadecimal,
+        Some("0o") => Radix::Octal,
+        Some("0b") => Radix::Binary,
+        _ => Radix::Decimal,
+    };
+
+    let line = lex.line;
+    let col = lex.col;
+    let trailing = lex.remainder()[value.len()..].find(|c: char| !c.is_ascii_alphanumeric() && c != '_');
+    let literal_len = trailing.map_or(lex.remainder().len(), |end| value.len() + end);
+    if literal_len > value.len() {
+        let invalid = lex.remainder()[value.len()..].chars().next().unwrap();
+        lex.error(format!("'{}' is not a valid {} digit in number literal", invalid, radix.name()));
+        value = &lex.remainder()[..literal_len];
+        lex.push(Token::new(TokenKind::ERROR, value.to_owned(), lex.source_name.clone(), line, col));
+        lex.advance_n(value.len() as u64);
+        return;
+    }
+
+    let mut token = Token::new(TokenKind::NUMBER, value.to_owned(), lex.source_name.clone(), line, col);
+    token.radix = Some(radix);
+    lex.push(token);
+    lex.advance_n(value.len() as u64);
 }
 
-fn new_line_handler(lex: &mut Lexer, regex: &Regex) {
-    if let Some(first_match) = regex.find(lex.remainder()) {
-        lex.advance_n(first_match.len() as u64);
-        lex.line += 1;
-        lex.col = 1;
+fn new_line_handler(lex: &mut Lexer) {
+    let rest = lex.remainder();
+    let len = line_break_len(rest);
+    lex.trivia(TriviaKind::Newline, &rest[..len]);
+    lex.new_line(len);
+}
+
+fn whitespace_handler(lex: &mut Lexer) {
+    let whitespace = &lex.remainder()[..prefix_len(lex.remainder(), |c| c.is_whitespace() && c != '\r' && c != '\n')];
+    let mut rest = whitespace;
+    while let Some(c) = rest.chars().next() {
+        let kind = match c {
+            ' ' => TriviaKind::Space,
+            '\t' => TriviaKind::Tab,
+            _ => TriviaKind::Whitespace,
+        };
+        let len = rest.find(|other: char| other != c).unwrap_or(rest.len());
+        lex.trivia(kind, &rest[..len]);
+        rest = &rest[len..];
+    }
+    lex.advance_n(whitespace.len() as u64);
+}
+
+fn line_comment_handler(lex: &mut Lexer) {
+    let comment = &lex.remainder()[..prefix_len(lex.remainder(), |c| c != '\r' && c != '\n')];
+    let kind = if comment.starts_with("///") && !comment.starts_with("////") {
+        TriviaKind::DocLineComment
+    } else {
+        TriviaKind::LineComment
+    };
+    lex.trivia(kind, comment);
+    lex.advance_n(comment.len() as u64);
+}
+
+fn block_comment_handler(lex: &mut Lexer) {
+    let start = lex.pos as usize;
+    lex_block_comment(lex);
+
+    let comment = &lex.source[start..lex.pos as usize];
+    let kind = if comment.starts_with("/**") && !comment.starts_with("/**/") {
+        TriviaKind::DocBlockComment
+    } else {
+        TriviaKind::BlockComment
+    };
+    lex.trivia(kind, comment);
+}
+
+// Skips a block comment, which may contain nested block comments
+fn lex_block_comment(lex: &mut Lexer) {
+    let (line, col) = (lex.line, lex.col);
+    let mut depth = 0;
+    loop {
+        let rest = lex.remainder();
+        if rest.starts_with("/*") {
+            depth += 1;
+            lex.advance_n(2);
+        } else if rest.starts_with("*/") {
+            depth -= 1;
+            lex.advance_n(2);
+            if depth == 0 {
+                return;
+            }
+        } else if line_break_len(rest) > 0 {
+            lex.new_line(line_break_len(rest));
+        } else if let Some(c) = rest.chars().next() {
+            lex.advance_n(c.len_utf8() as u64);
+        } else {
+            lex.error_at(String::from("unterminated '/*' comment"), line, col);
+            return;
+        }
+    }
+}
+
+// A piece of a string literal found while scanning it, literal text is cooked once the whole literal has been seen
+enum ScannedSegment {
+    Literal {
+        start: usize,
+        end: usize,
+        line: u64,
+        col: u64,
+    },
+    Interpolation {
+        tokens: Vec<Token>,
+    },
+}
+
+// Lexes a string literal starting at its opening delimiter, splitting its contents around interpolations.
+// Strings with extended delimiters, `#"..."#`, only treat a backslash followed by as many `#` as an escape
+fn lex_string(lex: &mut Lexer, delimiter_depth: usize, multiline: bool) {
+    let hashes = "#".repeat(delimiter_depth);
+    let quotes = if multiline { "\"\"\"" } else { "\"" };
+    let open = format!("{}{}", hashes, quotes);
+    let close = format!("{}{}", quotes, hashes);
+    let escape = format!("\\{}", hashes);
+
+    let (start, line, col) = (lex.pos as usize, lex.line, lex.col);
+    lex.advance_n(open.len() as u64);
+
+    let mut scanned = vec![];
+    let (mut segment_start, mut segment_line, mut segment_col) = (lex.pos as usize, lex.line, lex.col);
+    loop {
+        let rest = lex.remainder();
+        if rest.starts_with(&close) {
+            break;
+        }
+        match rest.chars().next() {
+            None => break,
+            Some('\r' | '\n') if !multiline => break,
+            Some('\r' | '\n') => lex.new_line(line_break_len(rest)),
+            Some('\\') if rest.starts_with(&escape) && rest[escape.len()..].starts_with('(') => {
+                scanned.push(ScannedSegment::Literal { start: segment_start, end: lex.pos as usize, line: segment_line, col: segment_col });
+                lex.advance_n(escape.len() as u64 + 1);
+                let tokens = lex_interpolation(lex, multiline);
+                scanned.push(ScannedSegment::Interpolation { tokens });
+                (segment_start, segment_line, segment_col) = (lex.pos as usize, lex.line, lex.col);
+            },
+            Some('\\') if rest.starts_with(&escape) => {
+                // A line break following a backslash is left for the newline handling above
+                let escaped = rest[escape.len()..].chars().next().filter(|c| *c != '\r' && *c != '\n');
+                lex.advance_n((escape.len() + escaped.map_or(0, |c| c.len_utf8())) as u64);
+            },
+            Some(c) => lex.advance_n(c.len_utf8() as u64),
+        }
+    }
+
+    if !lex.remainder().starts_with(&close) {
+        lex.error_at(String::from("unterminated string literal"), line, col);
+        lex.push(Token::new(TokenKind::ERROR, lex.source[start..lex.pos as usize].to_owned(), lex.source_name.clone(), line, col));
+        return;
+    }
+
+    let end = lex.pos as usize;
+    scanned.push(ScannedSegment::Literal { start: segment_start, end, line: segment_line, col: segment_col });
+    let (close_line, close_col) = (lex.line, lex.col);
+    lex.advance_n(close.len() as u64);
+
+    // Multi-line strings drop the line breaks after the opening and before the closing delimiter, and strip the
+    // indentation of the closing delimiter from every line
+    let mut content_start = start + open.len();
+    let mut content_end = end;
+    let mut indentation = None;
+    if multiline {
+        let opening_line = &lex.source[content_start..end];
+        match opening_line.find(['\r', '\n']).filter(|newline| opening_line[..*newline].trim_matches([' ', '\t']).is_empty()) {
+            Some(newline) => content_start += newline + line_break_len(&opening_line[newline..]),
+            None => lex.error_at(String::from("multi-line string literal content must begin on a new line"), line, col),
+        }
+
+        let closing_line = &lex.source[start..end];
+        match closing_line.rfind(['\r', '\n']).filter(|newline| closing_line[newline + 1..].trim_matches([' ', '\t']).is_empty()) {
+            Some(newline) if start + newline >= content_start => {
+                content_end = start + newline;
+                if closing_line[..=newline].ends_with("\r\n") && content_end > content_start {
+                    content_end -= 1;
+                }
+                indentation = Some(&lex.source[start + newline + 1..end]);
+            },
+            Some(_) => indentation = Some(&lex.source[content_start..end]),
+            None => lex.error_at(String::from("multi-line string literal closing delimiter must begin on a new line"), close_line, close_col),
+        }
     }
+
+    let mut segments = vec![];
+    let mut at_line_start = false;
+    for segment in scanned {
+        match segment {
+            ScannedSegment::Literal { start: segment_start, end: segment_end, mut line, mut col } => {
+                let value = lex.source[segment_start..segment_end].to_owned();
+                let mut cook_start = segment_start;
+                if segment_start < content_start {
+                    cook_start = content_start;
+                    line += 1;
+                    col = 1;
+                    at_line_start = true;
+                }
+                let cook_end = segment_end.min(content_end).max(cook_start);
+                let cooked = cook_segment(lex, cook_start..cook_end, line, col, &escape, indentation, at_line_start);
+                segments.push(StringSegment::Literal { value, cooked });
+            },
+            ScannedSegment::Interpolation { tokens } => {
+                segments.push(StringSegment::Interpolation { tokens });
+                at_line_start = false;
+            },
+        }
+    }
+
+    let value = lex.source[start + open.len()..end].to_owned();
+    let mut token = Token::new(TokenKind::STRING, value, lex.source_name.clone(), line, col);
+    token.string = Some(StringLiteral { segments, delimiter_depth: delimiter_depth as u64, multiline });
+    lex.push(token);
 }
 
-fn skip_handler(lex: &mut Lexer, regex: &Regex) {
-    if let Some(first_match) = regex.find(lex.remainder()) {
-        lex.advance_n(first_match.len() as u64);
+// Decodes the escapes in a range of literal string text, starting at `line` and `col`. Each line starting in
+// the range has the multi-line string's indentation stripped from it, and escaped line breaks are removed
+fn cook_segment(lex: &mut Lexer, range: std::ops::Range<usize>, mut line: u64, mut col: u64, escape: &str, indentation: Option<&str>, mut at_line_start: bool) -> String {
+    let raw = &lex.source[range];
+    let mut cooked = String::new();
+    let mut i = 0;
+    while i < raw.len() {
+        let rest = &raw[i..];
+        if at_line_start {
+            at_line_start = false;
+            if let Some(indentation) = indentation {
+                let line_text = &rest[..rest.find(['\r', '\n']).unwrap_or(rest.len())];
+                let stripped = if line_text.starts_with(indentation) {
+                    indentation.len()
+                } else if indentation.starts_with(line_text) {
+                    line_text.len() // Lines of only whitespace may be shorter than the indentation
+                } else {
+                    lex.error_at(String::from("insufficient indentation of line in multi-line string literal"), line, col);
+                    line_text.bytes().zip(indentation.bytes()).take_while(|(a, b)| a == b).count()
+                };
+                i += stripped;
+                col += stripped as u64;
+                continue;
+            }
+        }
+
+        if line_break_len(rest) > 0 {
+            // Every kind of line break is normalized to a line feed
+            cooked.push('\n');
+            i += line_break_len(rest);
+            line += 1;
+            col = 1;
+            at_line_start = true;
+        } else if let Some(escaped) = rest.strip_prefix(escape).filter(|escaped| line_break_len(escaped) > 0) {
+            i += escape.len() + line_break_len(escaped);
+            line += 1;
+            col = 1;
+            at_line_start = true;
+        } else if let Some(escaped) = rest.strip_prefix(escape) {
+            let len = match decode_escape(escaped) {
+                Ok((decoded, len)) => {
+                    cooked.push(decoded);
+                    len
+                },
+                Err((message, len)) => {
+                    lex.error_at(message, line, col);
+                    len
+                },
+            };
+            i += escape.len() + len;
+            col += rest[..escape.len() + len].chars().count() as u64;
+        } else {
+            let c = rest.chars().next().unwrap();
+            cooked.push(c);
+            i += c.len_utf8();
+            col += 1;
+        }
     }
+    cooked
 }
 
-fn block_comment_handler(lex: &mut Lexer, regex: &Regex) {
-    if let Some(first_match) = regex.find(lex.remainder()) {
-        let lines = first_match.as_str().lines().count() as u64;
-        lex.line += lines;
-        lex.advance_n(first_match.len() as u64);
+// Decodes the character escaped after a backslash, along with the length of the escape after the backslash.
+// Invalid escapes give an error along with the length to skip over
+fn decode_escape(escaped: &str) -> Result<(char, usize), (String, usize)> {
+    let decoded = match escaped.chars().next() {
+        Some('0') => '\0',
+        Some('\\') => '\\',
+        Some('t') => '\t',
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('"') => '"',
+        Some('\'') => '\'',
+        Some('u') => return decode_unicode_escape(escaped),
+        Some(c) => return Err((format!("invalid escape sequence '\\{}' in literal", c), c.len_utf8())),
+        None => return Err((String::from("invalid escape sequence in literal"), 0)),
+    };
+    Ok((decoded, 1))
+}
+
+// Decodes a `u{...}` escape of one to eight hex digits naming a unicode scalar
+fn decode_unicode_escape(escaped: &str) -> Result<(char, usize), (String, usize)> {
+    if !escaped[1..].starts_with('{') {
+        return Err((String::from("expected '{' in \\u{...} escape sequence"), 1));
+    }
+    let digits_len = escaped[2..].find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(escaped.len() - 2);
+    let digits = &escaped[2..2 + digits_len];
+    if !escaped[2 + digits_len..].starts_with('}') {
+        return Err((String::from("expected '}' in \\u{...} escape sequence"), 2 + digits_len));
+    }
+
+    let len = digits_len + 3;
+    if digits.is_empty() || digits.len() > 8 {
+        return Err((String::from("\\u{...} escape sequence expects between 1 and 8 hex digits"), len));
+    }
+    match u32::from_str_radix(digits, 16).ok().and_then(char::from_u32) {
+        Some(decoded) => Ok((decoded, len)),
+        None => Err((format!("invalid unicode scalar 'U+{}'", digits.to_uppercase()), len)),
     }
 }
 
-fn string_handler(lex: &mut Lexer, regex: &Regex) {
-    if let Some(first_match) = regex.find(lex.remainder()) {
-        lex.push(Token::new(TokenKind::STRING, first_match.as_str().trim_matches('\"').to_owned(), lex.source_name.clone(), lex.line, lex.col));
-        lex.advance_n(first_match.len() as u64);
+// Lexes the expression of an interpolation, `\(` having already been consumed, through its closing parenthesis.
+// An interpolation left open in a single-line string ends with its line, leaving the string unterminated
+fn lex_interpolation(lex: &mut Lexer, multiline: bool) -> Vec<Token> {
+    let (line, col) = (lex.line, lex.col);
+    let mut interpolation = Lexer::new(lex.pos, lex.line, lex.col, lex.source, lex.source_name.clone());
+    interpolation.interpolation_depth = Some(0);
+    interpolation.ends_at_line_break = !multiline;
+    interpolation.keep_trivia = lex.keep_trivia;
+    interpolation.run();
+    if !interpolation.finished {
+        lex.error_at(String::from("unterminated string interpolation"), line, col);
     }
+
+    interpolation.push_eof();
+    lex.pos = interpolation.pos;
+    lex.line = interpolation.line;
+    lex.col = interpolation.col;
+    lex.diagnostics.append(&mut interpolation.diagnostics);
+    interpolation.tokens
 }
 
-fn block_string_handler(lex: &mut Lexer, regex: &Regex) {
-    if let Some(first_match) = regex.find(lex.remainder()) {
-        lex.push(Token::new(TokenKind::STRING, first_match.as_str()[3..first_match.len()-3].to_owned(), lex.source_name.clone(), lex.line, lex.col));
-        lex.advance_n(first_match.len() as u64);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::token::tokens_to_source;
+
+    fn lex(source: &str) -> Vec<Token> {
+        let (tokens, diagnostics) = tokenize(source, String::from("test.swift"));
+        assert!(diagnostics.is_empty(), "unexpected diagnostics: {:?}", diagnostics);
+        tokens
+    }
+
+    fn kinds_and_values(tokens: &[Token]) -> Vec<(TokenKind, &str)> {
+        tokens.iter().map(|token| (token.kind, token.value.as_str())).collect()
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn positions_and_spans() {
+        let tokens = lex("let café = 1\n  foo");
+        let positions: Vec<_> = tokens.iter().map(|token| (token.line, token.col, token.span.start, token.span.end)).collect();
+        assert_eq!(positions, vec![(1, 1, 0, 3), (1, 5, 4, 9), (1, 10, 10, 11), (1, 12, 12, 13), (2, 3, 16, 19), (2, 6, 19, 19)]);
+        assert_eq!(tokens[1].kind, TokenKind::IDENTIFIER);
+        assert_eq!(tokens.last().unwrap().kind, TokenKind::EOF);
+    }
+
+    #[test]
+    fn operators_and_fixity() {
+        let tokens = lex("a + b; -c; d!; e&+=f; g <*> h");
+        let operators: Vec<_> = tokens.iter()
+            .filter(|token| token.fixity.is_some())
+            .map(|token| (token.kind, token.value.as_str(), token.fixity.unwrap()))
+            .collect();
+        assert_eq!(operators, vec![
+            (TokenKind::PLUS, "+", Fixity::Infix),
+            (TokenKind::MINUS, "-", Fixity::Prefix),
+            (TokenKind::NOT, "!", Fixity::Postfix),
+            (TokenKind::OVERFLOW_PLUS_EQUALS, "&+=", Fixity::Infix),
+            (TokenKind::OPERATOR, "<*>", Fixity::Infix),
+        ]);
+    }
+
+    #[test]
+    fn number_forms() {
+        let tokens = lex("0x1F 0b101 0o17 1_000 1.5e-3 0x1.8p1 t.0.1 0...1.5");
+        let numbers: Vec<_> = tokens.iter()
+            .filter(|token| token.kind == TokenKind::NUMBER)
+            .map(|token| (token.value.as_str(), token.radix.unwrap()))
+            .collect();
+        assert_eq!(numbers, vec![
+            ("0x1F", Radix::Hexadecimal),
+            ("0b101", Radix::Binary),
+            ("0o17", Radix::Octal),
+            ("1_000", Radix::Decimal),
+            ("1.5e-3", Radix::Decimal),
+            ("0x1.8p1", Radix::Hexadecimal),
+            ("0", Radix::Decimal),
+            ("1", Radix::Decimal),
+            ("0", Radix::Decimal),
+            ("1.5", Radix::Decimal),
+        ]);
+    }
+
+    #[test]
+    fn interpolated_string() {
+        let tokens = lex(r#"let s = "a\(b + 1)\n""#);
+        let string = tokens[3].string.as_ref().unwrap();
+        assert_eq!(tokens[3].kind, TokenKind::STRING);
+        assert_eq!(tokens[3].span, Span::new(8, 21));
+        assert_eq!(string.segments.len(), 3);
+        let StringSegment::Interpolation { tokens: interpolated } = &string.segments[1] else {
+            panic!("expected an interpolation");
+        };
+        assert_eq!(kinds_and_values(interpolated), vec![
+            (TokenKind::IDENTIFIER, "b"),
+            (TokenKind::PLUS, "+"),
+            (TokenKind::NUMBER, "1"),
+            (TokenKind::EOF, ""),
+        ]);
+        assert_eq!((interpolated[0].line, interpolated[0].col), (1, 13));
+        assert_eq!(string.cooked(), "a\n");
+    }
+
+    #[test]
+    fn raw_string() {
+        let tokens = lex(r###"#"a\n\#(b)"#"###);
+        let string = tokens[0].string.as_ref().unwrap();
+        assert_eq!(tokens[0].value, r"a\n\#(b)");
+        assert_eq!(string.delimiter_depth, 1);
+        assert!(string.is_raw() && string.is_interpolated());
+        assert_eq!(string.cooked(), r"a\n");
+    }
+
+    #[test]
+    fn multiline_string() {
+        let tokens = lex("let s = \"\"\"\n    one\n      two\n    \"\"\"\nx");
+        let string = tokens[3].string.as_ref().unwrap();
+        assert!(string.multiline);
+        assert_eq!(string.cooked(), "one\n  two");
+        assert_eq!((tokens[4].value.as_str(), tokens[4].line, tokens[4].col), ("x", 5, 1));
+    }
+
+    #[test]
+    fn unterminated_interpolation_ends_with_its_line() {
+        let (tokens, diagnostics) = tokenize("let s = \"\\(a\nlet b = 1", String::from("test.swift"));
+        assert_eq!(tokens[3].kind, TokenKind::ERROR);
+        assert_eq!(tokens[3].span, Span::new(8, 12));
+        assert_eq!(kinds_and_values(&tokens[4..]), vec![
+            (TokenKind::LET, "let"),
+            (TokenKind::IDENTIFIER, "b"),
+            (TokenKind::ASSIGNMENT, "="),
+            (TokenKind::NUMBER, "1"),
+            (TokenKind::EOF, ""),
+        ]);
+        let messages: Vec<_> = diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect();
+        assert_eq!(messages, vec!["unterminated string interpolation", "unterminated string literal"]);
+    }
+
+    #[test]
+    fn nested_comments() {
+        let tokens = lex("/* a /* b */ c */ x // y\nz");
+        assert_eq!(kinds_and_values(&tokens), vec![(TokenKind::IDENTIFIER, "x"), (TokenKind::IDENTIFIER, "z"), (TokenKind::EOF, "")]);
+        assert_eq!((tokens[0].line, tokens[0].col), (1, 19));
+        assert_eq!((tokens[1].line, tokens[1].col), (2, 1));
+    }
+
+    #[test]
+    fn crlf_line_breaks() {
+        let tokens = lex("let a\r\nlet b\rlet c");
+        let positions: Vec<_> = tokens.iter().map(|token| (token.value.as_str(), token.line, token.col)).collect();
+        assert_eq!(positions, vec![("let", 1, 1), ("a", 1, 5), ("let", 2, 1), ("b", 2, 5), ("let", 3, 1), ("c", 3, 5), ("", 3, 6)]);
+    }
+
+    #[test]
+    fn regex_literals() {
+        let tokens = lex("let r = /[a-z]+/\nlet e = #/a/b/#\nlet d = x / y / z");
+        let regexes: Vec<_> = tokens.iter()
+            .filter(|token| token.kind == TokenKind::REGEX)
+            .map(|token| (token.value.as_str(), token.regex.unwrap().delimiter_depth))
+            .collect();
+        assert_eq!(regexes, vec![("[a-z]+", 0), ("a/b", 1)]);
+        assert_eq!(tokens.iter().filter(|token| token.kind == TokenKind::SLASH).count(), 2);
+    }
+
+    #[test]
+    fn doc_comments_attach_alike_with_and_without_trivia() {
+        let source = "/// Leads a\nlet a = 1 /// Trails 1\n/** Leads b */ let b = /** Trails = */ 2\n\n/// Leads EOF";
+        let docs = |tokens: Vec<Token>| -> Vec<_> {
+            let is_doc = |trivia: &&Trivia| matches!(trivia.kind, TriviaKind::DocLineComment | TriviaKind::DocBlockComment);
+            tokens.into_iter()
+                .map(|token| {
+                    let leading: Vec<_> = token.leading_trivia.iter().filter(is_doc).map(|trivia| trivia.text.clone()).collect();
+                    let trailing: Vec<_> = token.trailing_trivia.iter().filter(is_doc).map(|trivia| trivia.text.clone()).collect();
+                    (token.value, leading, trailing)
+                })
+                .filter(|(_, leading, trailing)| !leading.is_empty() || !trailing.is_empty())
+                .collect()
+        };
+        let plain = docs(lex(source));
+        assert_eq!(plain, docs(tokenize_with_trivia(source, String::from("test.swift")).0));
+        assert_eq!(plain, vec![
+            (String::from("let"), vec![String::from("/// Leads a")], vec![]),
+            (String::from("1"), vec![], vec![String::from("/// Trails 1")]),
+            (String::from("let"), vec![String::from("/** Leads b */")], vec![]),
+            (String::from("="), vec![], vec![String::from("/** Trails = */")]),
+            (String::from(""), vec![String::from("/// Leads EOF")], vec![]),
+        ]);
+    }
+
+    #[test]
+    fn lossless_round_trip() {
+        let source = "/// Doc\r\n@available(*, unavailable)\nfunc f() {\n\tlet s = #\"\\#(a /* c */)\"# + \"\"\"\n  x\n  \"\"\" // end\n}\n";
+        let (tokens, _) = tokenize_with_trivia(source, String::from("test.swift"));
+        assert_eq!(tokens_to_source(&tokens), source);
+        let plain: Vec<_> = lex(source).into_iter().map(|token| (token.kind, token.value, token.span)).collect();
+        let with_trivia: Vec<_> = tokens.into_iter().map(|token| (token.kind, token.value, token.span)).collect();
+        assert_eq!(plain, with_trivia);
+    }
+}
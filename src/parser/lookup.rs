@@ -1,6 +1,6 @@
 use std::{collections::HashMap, sync::{Mutex, MutexGuard, OnceLock}};
 
-use crate::{ast::{expressions::{parse_primary_expr, Expr}, statements::{parse_prefix_stmt, parse_var_decl_stmt, Stmt}}, lexer::token::TokenKind};
+use crate::{ast::{expressions::{parse_key_path_expr, parse_prefix_expr, parse_primary_expr, Expr}, if_config::{parse_if_config_stmt, parse_unmatched_if_config_stmt}, statements::{parse_attributed_stmt, parse_prefix_stmt, parse_var_decl_stmt, Stmt}}, lexer::token::TokenKind};
 use super::{expressions::parse_bracket_expr, parser::Parser};
 
 type StmtHandler = fn (p: &mut Parser) -> Stmt;
@@ -60,7 +60,12 @@ pub fn register_lookups() {
 
     nud_reg(TokenKind::NUMBER, PRIMARY, parse_primary_expr);
     nud_reg(TokenKind::STRING, PRIMARY, parse_primary_expr);
+    nud_reg(TokenKind::REGEX, PRIMARY, parse_primary_expr);
     nud_reg(TokenKind::IDENTIFIER, PRIMARY, parse_primary_expr);
+    nud_reg(TokenKind::DOLLAR_IDENTIFIER, PRIMARY, parse_primary_expr);
+    nud_reg(TokenKind::BACKSLASH, PRIMARY, parse_key_path_expr);
+    // A prefix `&` marks an argument passed inout
+    nud_reg(TokenKind::AMPERSAND, UNARY, parse_prefix_expr);
     nud_reg(TokenKind::OPEN_BRACKET, PRIMARY, parse_bracket_expr);
 
     
@@ -73,6 +78,12 @@ pub fn register_lookups() {
     stmt_reg(TokenKind::STATIC,parse_prefix_stmt);
     stmt_reg(TokenKind::FINAL,parse_prefix_stmt);
 
+    stmt_reg(TokenKind::ANNOTATION, parse_attributed_stmt);
+    stmt_reg(TokenKind::POUND_IF, parse_if_config_stmt);
+    stmt_reg(TokenKind::POUND_ELSEIF, parse_unmatched_if_config_stmt);
+    stmt_reg(TokenKind::POUND_ELSE, parse_unmatched_if_config_stmt);
+    stmt_reg(TokenKind::POUND_ENDIF, parse_unmatched_if_config_stmt);
+
     stmt_reg(TokenKind::VAR, parse_var_decl_stmt);
     stmt_reg(TokenKind::LET, parse_var_decl_stmt);
 }
@@ -1,6 +1,6 @@
 use std::{path::Path, sync::Mutex};
 
-use crate::{ast::{expressions::Expr, statements::Stmt}, lexer::{swift_tokenizer::Tokenizer, token::{token_can_be_name, Token, TokenKind}}, parser::lookup::{bp, led, nud}};
+use crate::{ast::{docs::parse_doc_comment, expressions::Expr, statements::{attach_doc, parse_prefix_stmt, starts_with_contextual_modifier, Stmt}}, lexer::{diagnostic::Diagnostic, swift_tokenizer::Tokenizer, token::{operator_to_token, token_can_be_name, ContextualKeyword, Span, Token, TokenKind}}, parser::lookup::{bp, led, nud}};
 
 use super::{lookup::{register_lookups, stmt, BindingPower, DEFAULT_BP}, types::register_types_lookup};
 
@@ -8,17 +8,24 @@ static LOOKUPS_MADE: Mutex<bool> = Mutex::new(false);
 
 pub struct Parser {
     tokens: Vec<Token>,
+    diagnostics: Vec<Diagnostic>,
     pos: u64,
 }
 
 impl Parser {
-    fn new(tokens: Vec<Token>) -> Parser {
+    pub(crate) fn new(tokens: Vec<Token>, diagnostics: Vec<Diagnostic>) -> Parser {
         Parser {
             tokens,
+            diagnostics,
             pos: 0
         }
     }
 
+    #[cfg(test)]
+    pub(crate) fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     pub fn current_token(&self) -> &Token {
         &self.tokens[self.pos as usize]
     }
@@ -53,6 +60,37 @@ impl Parser {
         panic!("Expected token {:?} but found {:?}", kind, token.kind);
     }
 
+    pub fn error(&mut self, token: &Token, message: String) {
+        self.diagnostics.push(Diagnostic::new(message, token.file.clone(), token.line, token.col));
+    }
+
+    // Splits the current operator token after its first `len` bytes, for places where the grammar only wants
+    // the start of an operator, such as the `>` closing a generic inside of `>>`
+    pub fn split_current_token(&mut self, len: usize) {
+        let token = self.current_token().clone();
+        let (head, tail) = token.value.split_at(len);
+        let head_kind = operator_to_token(head).unwrap_or(&TokenKind::OPERATOR);
+        let tail_kind = operator_to_token(tail).unwrap_or(&TokenKind::OPERATOR);
+
+        let mut head_token = Token::new(*head_kind, head.to_string(), token.file.clone(), token.line, token.col);
+        head_token.span = Span::new(token.span.start, token.span.start + len as u64);
+        let mut tail_token = Token::new(*tail_kind, tail.to_string(), token.file, token.line, token.col + head.chars().count() as u64);
+        tail_token.span = Span::new(token.span.start + len as u64, token.span.end);
+
+        let pos = self.pos as usize;
+        self.tokens[pos] = head_token;
+        self.tokens.insert(pos + 1, tail_token);
+    }
+
+    // The contextual keyword the current token may be promoted to, escaped identifiers never being promoted
+    pub fn contextual_keyword(&self) -> Option<ContextualKeyword> {
+        let token = self.current_token();
+        if token.kind == TokenKind::IDENTIFIER {
+            return token.contextual_keyword;
+        }
+        None
+    }
+
     pub fn has_pattern(&mut self, pattern: &[TokenKind]) -> bool {
         for i in 0..pattern.len() {
             let token = &self.tokens[self.pos as usize + i];
@@ -66,33 +104,51 @@ impl Parser {
     }
 }
 
-pub fn parse(file: &Path, cache: bool) -> Stmt {
+// Parses the file into a block statement, returning it along with any recoverable errors found in the file
+pub fn parse(file: &Path, cache: bool) -> (Stmt, Vec<Diagnostic>) {
+    let (tokens, diagnostics) = create_tokenizer(cache).tokenize(file).expect("Failed to tokenize file");
+    parse_tokens(tokens, diagnostics)
+}
+
+// Parses source held in memory like `parse` does a file, the name standing in for its path
+pub fn parse_str(source: &str, name: &str, cache: bool) -> (Stmt, Vec<Diagnostic>) {
+    let (tokens, diagnostics) = create_tokenizer(cache).tokenize_str(source, name).expect("Failed to tokenize source");
+    parse_tokens(tokens, diagnostics)
+}
+
+fn create_tokenizer(cache: bool) -> Tokenizer {
+    if cache {
+        return Tokenizer::new_cached(Path::new("./cache.txt"));
+    }
+    Tokenizer::new_non_cached()
+}
+
+fn parse_tokens(tokens: Vec<Token>, diagnostics: Vec<Diagnostic>) -> (Stmt, Vec<Diagnostic>) {
     if !*LOOKUPS_MADE.lock().unwrap() {
         *LOOKUPS_MADE.lock().unwrap() = true;
         register_lookups();
         register_types_lookup();
     }
 
-    let mut tokenizer: Tokenizer;
-    if cache {
-        tokenizer = Tokenizer::new_cached(Path::new("./cache.txt"));
-    } else {
-        tokenizer = Tokenizer::new_non_cached();
-    }
-    let tokens = tokenizer.tokenize(file).expect("Failed to tokenize file");
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(tokens, diagnostics);
     let mut body = vec![];
 
     while parser.has_tokens() {
         body.push(Box::new(parse_stmt(&mut parser)));
     }
 
-    Stmt::BlockStmt{ body }
+    (Stmt::BlockStmt{ body }, parser.diagnostics)
 }
 
 pub fn parse_stmt(p: &mut Parser) -> Stmt {
-    if let Some(stmt_fn) = stmt(&p.current_token().kind) {
-        let stmt = (stmt_fn)(p);
+    let mut stmt_fn = stmt(&p.current_token().kind);
+    if stmt_fn.is_none() && starts_with_contextual_modifier(p) {
+        stmt_fn = Some(parse_prefix_stmt);
+    }
+    if let Some(stmt_fn) = stmt_fn {
+        let doc = parse_doc_comment(&p.current_token().leading_trivia);
+        let mut stmt = (stmt_fn)(p);
+        attach_doc(&mut stmt, doc);
         if p.current_token().kind == TokenKind::SEMI_COLON { p.advance(); }
         return stmt;
     }
@@ -106,6 +162,24 @@ fn parse_expr_stmt(p: &mut Parser) -> Stmt {
     Stmt::ExpressionStmt{ expression: Box::new(expression) }
 }
 
+// Parses tokens lexed apart from the main stream, such as those of a string interpolation, as a single expression
+pub fn parse_nested_expr(p: &mut Parser, tokens: Vec<Token>) -> Expr {
+    let mut nested = Parser::new(tokens, vec![]);
+    if !nested.has_tokens() {
+        let token = nested.current_token().clone();
+        p.error(&token, String::from("expected an expression"));
+        return Expr::None;
+    }
+
+    let expr = parse_expr(&mut nested, DEFAULT_BP);
+    if nested.has_tokens() {
+        let token = nested.current_token().clone();
+        nested.error(&token, format!("unexpected '{}' after expression", token.value));
+    }
+    p.diagnostics.append(&mut nested.diagnostics);
+    expr
+}
+
 pub fn parse_expr(p: &mut Parser, starting_bp: BindingPower) -> Expr {
     let token_kind = p.current_token().kind;
 
@@ -113,16 +187,29 @@ pub fn parse_expr(p: &mut Parser, starting_bp: BindingPower) -> Expr {
         let mut left = (nud)(p);
 
         while bp(&token_kind).expect("No bp found") > starting_bp && p.has_tokens() {
-            let token_kind = p.current_token().kind;
-            if let Some(led_fn) = led(&token_kind) {
-                left = led_fn(p, left, starting_bp);
-            } else {
-                panic!("Led handler expected for token {:?}", token_kind);
-            }
+            // A token that cannot continue the expression ends it, leaving what follows to the caller
+            let Some(led_fn) = led(&p.current_token().kind) else { break };
+            left = led_fn(p, left, starting_bp);
         }
 
         return left
     }
 
     panic!("Nud handler expected for token {:?}", token_kind);
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolation_must_be_a_single_expression() {
+        let (_, diagnostics) = parse_str("let s = \"\\(a b)\"", "test.swift", false);
+        assert_eq!(diagnostics.len(), 1, "{:?}", diagnostics);
+        assert_eq!(diagnostics[0].message, "unexpected 'b' after expression");
+        assert_eq!((diagnostics[0].line, diagnostics[0].col), (1, 14));
+
+        let (_, diagnostics) = parse_str("let s = \"\\(a)\"", "test.swift", false);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+}
@@ -1,6 +1,6 @@
 use std::{collections::HashMap, sync::{Mutex, OnceLock}};
 
-use crate::{ast::types::{parse_type, Type}, lexer::token::TokenKind};
+use crate::{ast::{attributes::parse_attributes, types::{parse_type, Type}}, lexer::token::TokenKind};
 
 use super::{lookup::{get_map, BindingPower, MEMBER, PRIMARY}, parser::Parser};
 
@@ -40,6 +40,7 @@ pub fn register_types_lookup() {
     nud_reg(TokenKind::OPEN_PAREN, MEMBER, parse_tuple_type);
     nud_reg(TokenKind::ANY, PRIMARY, parse_prefixed_type);
     nud_reg(TokenKind::SOME, PRIMARY, parse_prefixed_type);
+    nud_reg(TokenKind::ANNOTATION, PRIMARY, parse_attributed_type);
 }
 
 pub fn nud(kind: &TokenKind) -> Option<NudHandler> {
@@ -58,6 +59,11 @@ fn parse_identifier_type(p: &mut Parser) -> Type {
     Type::SymbolType { modifier: None, value: token.value }
 }
 
+fn parse_attributed_type(p: &mut Parser) -> Type {
+    let attributes = parse_attributes(p);
+    Type::AttributedType { attributes, underlying: Box::new(parse_type(p)) }
+}
+
 fn parse_generic_types(p: &mut Parser) -> Vec<Box<Type>> {
     let mut result = vec![];
     _ = p.expect(TokenKind::LESS);
@@ -66,6 +72,9 @@ fn parse_generic_types(p: &mut Parser) -> Vec<Box<Type>> {
         _ = p.advance();
         result.push(Box::new(parse_type(p)));
     }
+    if p.current_token().kind != TokenKind::GREATER && p.current_token().value.starts_with('>') {
+        p.split_current_token(1);
+    }
     p.expect(TokenKind::GREATER);
     result
 }